@@ -1,16 +1,65 @@
+use base64::alphabet;
+use base64::engine::general_purpose::GeneralPurpose;
+use base64::engine::general_purpose::GeneralPurposeConfig;
+use base64::engine::DecodePaddingMode;
 use base64::prelude::BASE64_STANDARD;
 use base64::Engine;
 
+use crate::decode::bytes_to_unicode;
+use crate::decode::DecodeError;
+use crate::decode::Decoding;
+
+// 解码时忽略 padding，订阅里缺少或多余 = 的情况都很常见
+const STANDARD_INDIFFERENT: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+const URL_SAFE_INDIFFERENT: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// 宽松的 base64 解码，解码失败或解码结果不是 UTF-8 文本时原样返回
+/// 只用于既可能是 base64 也可能是明文的字段，如 SIP002 格式 ss 链接的 userinfo、
+/// Shadowrocket 格式 vmess 链接中带参数的正文，调用方需要能处理原样返回的明文
+/// 必须是 base64 的字段使用 try_base64decode_text
 pub fn base64decode(content: &str) -> String {
-    let cleaned = content.replace("_", "/");
-    let padded = match cleaned.len() % 4 {
-        0 => cleaned,
-        n => cleaned + &"=".repeat(4 - n),
-    };
-    match BASE64_STANDARD.decode(padded.as_bytes()) {
-        Ok(data) => String::from_utf8(data).unwrap(),
-        Err(_) => content.to_string(),
+    try_base64decode_text(content).unwrap_or_else(|_| content.to_string())
+}
+
+/// 严格解码 base64 文本，解码失败或解码结果不是 UTF-8 文本时返回错误
+/// 不尝试 GBK，以免把原文解成乱码
+pub fn try_base64decode_text(content: &str) -> Result<String, DecodeError> {
+    let (data, _) = try_base64decode(content)?;
+    bytes_to_unicode(&data).map(|(text, _)| text)
+}
+
+/// 严格的 base64 解码，支持标准、URL-safe 以及按行折断的 MIME 格式
+/// 返回解码后的字节以及识别出的格式
+pub fn try_base64decode(content: &str) -> Result<(Vec<u8>, Decoding), DecodeError> {
+    let trimmed = content.trim();
+    if trimmed.is_empty() {
+        return Err(DecodeError::new("empty base64 content"));
     }
+    let mut decoding = Decoding::Base64Standard;
+    let cleaned: String = if trimmed.contains(char::is_whitespace) {
+        decoding = Decoding::Base64Mime;
+        trimmed.split_whitespace().collect()
+    } else {
+        trimmed.to_string()
+    };
+    let result = if cleaned.contains(['-', '_']) {
+        if decoding == Decoding::Base64Standard {
+            decoding = Decoding::Base64UrlSafe;
+        }
+        URL_SAFE_INDIFFERENT.decode(cleaned.trim_end_matches('=').as_bytes())
+    } else {
+        STANDARD_INDIFFERENT.decode(cleaned.trim_end_matches('=').as_bytes())
+    };
+    result
+        .map(|data| (data, decoding))
+        .map_err(|e| DecodeError::new(&format!("invalid base64 content: {}", e)))
 }
 
 pub fn base64encode(content: String) -> String {
@@ -45,4 +94,42 @@ mod tests {
         let str = base64decode(String::from("aGVsbG8").as_str());
         assert_eq!(str, String::from("hello"))
     }
+
+    #[test]
+    fn test_try_base64decode_variants() {
+        // "subs?>" 在标准字母表中包含 + 和 /
+        let (data, decoding) = try_base64decode("c3Vicz8+").unwrap();
+        assert_eq!(data, b"subs?>");
+        assert_eq!(decoding, Decoding::Base64Standard);
+
+        let (data, decoding) = try_base64decode("c3Vicz8-").unwrap();
+        assert_eq!(data, b"subs?>");
+        assert_eq!(decoding, Decoding::Base64UrlSafe);
+
+        let (data, decoding) = try_base64decode("aGVs\r\nbG8=\n").unwrap();
+        assert_eq!(data, b"hello");
+        assert_eq!(decoding, Decoding::Base64Mime);
+
+        assert!(try_base64decode("ss://not base64!").is_err());
+        assert!(try_base64decode("").is_err());
+    }
+
+    #[test]
+    fn test_try_base64decode_text() {
+        assert_eq!(try_base64decode_text("aGVsbG8").unwrap(), "hello");
+        assert!(try_base64decode_text("aes-256-gcm:pwd").is_err());
+        assert!(try_base64decode_text("Q1GUZ7VDPZOASC9H").is_err());
+    }
+
+    #[test]
+    fn test_base64decode_no_gbk_fallback() {
+        // 该密码恰好是合法的 base64，解码后的字节能被 GBK 解码成乱码，但不是 UTF-8
+        assert_eq!(base64decode("Q1GUZ7VDPZOASC9H"), "Q1GUZ7VDPZOASC9H");
+    }
+
+    #[test]
+    fn test_base64decode_non_utf8() {
+        // 0xff 0xfe 0xfd 既不是 UTF-8 也不是 GBK，原样返回而不是 panic
+        assert_eq!(base64decode("//79"), "//79");
+    }
 }
@@ -0,0 +1,277 @@
+use std::fmt;
+use std::io::Read;
+
+use encoding_rs::GBK;
+use encoding_rs::UTF_16BE;
+use encoding_rs::UTF_16LE;
+use flate2::read::DeflateDecoder;
+use flate2::read::GzDecoder;
+use flate2::read::ZlibDecoder;
+
+use crate::base64::try_base64decode;
+
+const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+const UTF16LE_BOM: &[u8] = &[0xff, 0xfe];
+const UTF16BE_BOM: &[u8] = &[0xfe, 0xff];
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// 解码订阅内容时实际应用的步骤
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decoding {
+    Gzip,
+    Deflate,
+    Brotli,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    Gbk,
+    Base64Standard,
+    Base64UrlSafe,
+    Base64Mime,
+}
+
+#[derive(Debug)]
+pub struct DecodeError {
+    message: String,
+}
+
+impl DecodeError {
+    pub fn new(message: &str) -> Self {
+        DecodeError {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// 解码后的文本以及按顺序应用过的解码步骤
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedContent {
+    pub content: String,
+    pub decodings: Vec<Decoding>,
+}
+
+/// 将订阅响应体或本地文件解码为文本
+/// 1. 按 Content-Encoding 或文件头解压 gzip/deflate/brotli
+/// 2. 识别 BOM，去除 UTF-8 BOM 或按 UTF-16 解码
+/// 3. 非 UTF-8 内容尝试使用 GBK 解码
+pub fn decode_bytes(
+    bytes: &[u8],
+    content_encoding: Option<&str>,
+) -> Result<DecodedContent, DecodeError> {
+    let mut decodings = Vec::new();
+    let data = decompress(bytes, content_encoding, &mut decodings)?;
+    let (content, charset) = bytes_to_string(&data)?;
+    decodings.extend(charset);
+    Ok(DecodedContent { content, decodings })
+}
+
+/// 严格解码 base64 订阅内容，解码结果同样经过 BOM 和字符集处理
+pub fn decode_base64_content(content: &str) -> Result<DecodedContent, DecodeError> {
+    let (data, base64) = try_base64decode(content)?;
+    let mut decodings = vec![base64];
+    let (content, charset) = bytes_to_string(&data)?;
+    decodings.extend(charset);
+    Ok(DecodedContent { content, decodings })
+}
+
+/// 解码节点名称中的百分号编码，兼容部分机场使用 GBK 编码的备注
+pub fn decode_remark(remark: &str) -> String {
+    let data = urlencoding::decode_binary(remark.as_bytes());
+    match bytes_to_string(&data) {
+        Ok((name, _)) => name,
+        Err(_) => String::from_utf8_lossy(&data).to_string(),
+    }
+}
+
+/// 按 BOM、UTF-8、GBK 的顺序将字节转为文本，全部失败时返回错误
+pub fn bytes_to_string(data: &[u8]) -> Result<(String, Option<Decoding>), DecodeError> {
+    // 带 UTF-8 BOM 但内容无效时不再尝试 GBK
+    if data.starts_with(UTF8_BOM) {
+        return bytes_to_unicode(data);
+    }
+    if let Ok(result) = bytes_to_unicode(data) {
+        return Ok(result);
+    }
+    let (text, had_errors) = GBK.decode_without_bom_handling(data);
+    if !had_errors {
+        return Ok((text.to_string(), Some(Decoding::Gbk)));
+    }
+    Err(DecodeError::new("content is neither utf-8 nor gbk"))
+}
+
+/// 按 BOM 和 UTF-8 将字节转为文本，不尝试 GBK
+/// 任意字节都可能被 GBK 解码成乱码，只有能确认解码结果有效时才应使用 GBK
+pub fn bytes_to_unicode(data: &[u8]) -> Result<(String, Option<Decoding>), DecodeError> {
+    if let Some(rest) = data.strip_prefix(UTF8_BOM) {
+        return match String::from_utf8(rest.to_vec()) {
+            Ok(text) => Ok((text, Some(Decoding::Utf8Bom))),
+            Err(_) => Err(DecodeError::new("invalid utf-8 content after bom")),
+        };
+    }
+    if let Some(rest) = data.strip_prefix(UTF16LE_BOM) {
+        let (text, had_errors) = UTF_16LE.decode_without_bom_handling(rest);
+        if !had_errors {
+            return Ok((text.to_string(), Some(Decoding::Utf16Le)));
+        }
+    }
+    if let Some(rest) = data.strip_prefix(UTF16BE_BOM) {
+        let (text, had_errors) = UTF_16BE.decode_without_bom_handling(rest);
+        if !had_errors {
+            return Ok((text.to_string(), Some(Decoding::Utf16Be)));
+        }
+    }
+    match std::str::from_utf8(data) {
+        Ok(text) => Ok((text.to_string(), None)),
+        Err(_) => Err(DecodeError::new("content is not utf-8")),
+    }
+}
+
+fn decompress(
+    bytes: &[u8],
+    content_encoding: Option<&str>,
+    decodings: &mut Vec<Decoding>,
+) -> Result<Vec<u8>, DecodeError> {
+    let encoding = content_encoding.map(|e| e.trim().to_ascii_lowercase());
+    match encoding.as_deref() {
+        Some("gzip") | Some("x-gzip") => {
+            decodings.push(Decoding::Gzip);
+            read_all(GzDecoder::new(bytes))
+        }
+        Some("deflate") => {
+            decodings.push(Decoding::Deflate);
+            // HTTP 的 deflate 规范上是 zlib 格式，但不少服务端直接返回裸 deflate
+            read_all(ZlibDecoder::new(bytes)).or_else(|_| read_all(DeflateDecoder::new(bytes)))
+        }
+        Some("br") => {
+            decodings.push(Decoding::Brotli);
+            read_all(brotli::Decompressor::new(bytes, 4096))
+        }
+        _ => {
+            // 没有 Content-Encoding 时按文件头识别，如直接托管的 .gz 订阅文件
+            if bytes.starts_with(GZIP_MAGIC) {
+                if let Ok(data) = read_all(GzDecoder::new(bytes)) {
+                    decodings.push(Decoding::Gzip);
+                    return Ok(data);
+                }
+            }
+            if is_zlib_header(bytes) {
+                if let Ok(data) = read_all(ZlibDecoder::new(bytes)) {
+                    decodings.push(Decoding::Deflate);
+                    return Ok(data);
+                }
+            }
+            if std::str::from_utf8(bytes).is_err() {
+                // brotli 没有文件头，只在内容不是文本时尝试
+                if let Ok(data) = read_all(brotli::Decompressor::new(bytes, 4096)) {
+                    if !data.is_empty() {
+                        decodings.push(Decoding::Brotli);
+                        return Ok(data);
+                    }
+                }
+            }
+            Ok(bytes.to_vec())
+        }
+    }
+}
+
+fn is_zlib_header(bytes: &[u8]) -> bool {
+    bytes.len() >= 2
+        && bytes[0] & 0x0f == 8
+        && (u16::from(bytes[0]) << 8 | u16::from(bytes[1])) % 31 == 0
+}
+
+fn read_all<R: Read>(mut reader: R) -> Result<Vec<u8>, DecodeError> {
+    let mut data = Vec::new();
+    reader
+        .read_to_end(&mut data)
+        .map_err(|e| DecodeError::new(&format!("decompress failed: {}", e)))?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    const LINK: &str = "ss://YWVzLTEyOC1nY206ZDljNTc3MzI4ZmIzNDlmZQ==@120.232.73.68:40676#HK";
+
+    #[test]
+    fn test_decode_gzip() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(LINK.as_bytes()).unwrap();
+        let data = encoder.finish().unwrap();
+
+        let decoded = decode_bytes(&data, None).unwrap();
+        assert_eq!(decoded.content, LINK);
+        assert_eq!(decoded.decodings, vec![Decoding::Gzip]);
+
+        let decoded = decode_bytes(&data, Some("gzip")).unwrap();
+        assert_eq!(decoded.content, LINK);
+    }
+
+    #[test]
+    fn test_decode_deflate() {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(LINK.as_bytes()).unwrap();
+        let data = encoder.finish().unwrap();
+
+        let decoded = decode_bytes(&data, Some("deflate")).unwrap();
+        assert_eq!(decoded.content, LINK);
+        assert_eq!(decoded.decodings, vec![Decoding::Deflate]);
+    }
+
+    #[test]
+    fn test_decode_brotli() {
+        let mut data = Vec::new();
+        {
+            let mut writer = brotli::CompressorWriter::new(&mut data, 4096, 5, 22);
+            writer.write_all(LINK.as_bytes()).unwrap();
+        }
+        let decoded = decode_bytes(&data, Some("br")).unwrap();
+        assert_eq!(decoded.content, LINK);
+        assert_eq!(decoded.decodings, vec![Decoding::Brotli]);
+    }
+
+    #[test]
+    fn test_decode_bom_and_gbk() {
+        let mut data = UTF8_BOM.to_vec();
+        data.extend_from_slice(LINK.as_bytes());
+        let decoded = decode_bytes(&data, None).unwrap();
+        assert_eq!(decoded.content, LINK);
+        assert_eq!(decoded.decodings, vec![Decoding::Utf8Bom]);
+
+        let (gbk, _, _) = GBK.encode("香港节点");
+        let decoded = decode_bytes(&gbk, None).unwrap();
+        assert_eq!(decoded.content, "香港节点");
+        assert_eq!(decoded.decodings, vec![Decoding::Gbk]);
+    }
+
+    #[test]
+    fn test_decode_base64_content() {
+        let decoded = decode_base64_content("c3M6Ly9ZV1Z6TFRFeU9DMW5ZMjA2WkRsak5UYzNNekk0Wm1Jek5EbG1aUT09QDEyMC4yMzIuNzMuNjg6NDA2NzYjSEs=").unwrap();
+        assert_eq!(decoded.content, LINK);
+        assert_eq!(decoded.decodings, vec![Decoding::Base64Standard]);
+
+        assert!(decode_base64_content(LINK).is_err());
+    }
+
+    #[test]
+    fn test_decode_remark() {
+        assert_eq!(decode_remark("%E9%A6%99%E6%B8%AF%2001"), "香港 01");
+        // GBK 编码的「香港」
+        assert_eq!(decode_remark("%CF%E3%B8%DB"), "香港");
+    }
+}
@@ -1,6 +1,11 @@
 pub mod base64;
+pub mod decode;
 pub mod protocol;
+pub mod region;
 pub mod sub;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod transform;
 
 pub fn add(left: u64, right: u64) -> u64 {
     left + right
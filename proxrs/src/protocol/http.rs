@@ -54,6 +54,10 @@ impl ProxyAdapter for Http {
         &self.server
     }
 
+    fn get_port(&self) -> u16 {
+        self.port
+    }
+
     fn to_link(&self) -> String {
         todo!()
     }
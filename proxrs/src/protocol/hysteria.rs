@@ -81,6 +81,10 @@ impl ProxyAdapter for Hysteria {
         &self.server
     }
 
+    fn get_port(&self) -> u16 {
+        self.port.unwrap_or_default()
+    }
+
     fn to_link(&self) -> String {
         todo!()
     }
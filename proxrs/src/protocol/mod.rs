@@ -23,6 +23,7 @@ use serde::Serialize;
 use serde_json::json;
 use serde_json::Value;
 
+use crate::decode::DecodeError;
 use crate::protocol::http::Http;
 use crate::protocol::hysteria::Hysteria;
 use crate::protocol::hysteria2::Hysteria2;
@@ -94,10 +95,19 @@ impl fmt::Display for UnsupportedLinkError {
 
 impl std::error::Error for UnsupportedLinkError {}
 
+impl From<DecodeError> for UnsupportedLinkError {
+    fn from(e: DecodeError) -> Self {
+        UnsupportedLinkError {
+            message: e.to_string(),
+        }
+    }
+}
+
 pub trait ProxyAdapter: ProxyAdapterClone {
     fn get_name(&self) -> &str;
     fn set_name(&mut self, name: &str);
     fn get_server(&self) -> &str;
+    fn get_port(&self) -> u16;
     fn to_link(&self) -> String;
     fn from_link(link: String) -> Result<Self, UnsupportedLinkError>
     where
@@ -134,6 +144,8 @@ impl Clone for Box<dyn ProxyAdapter> {
 pub struct Proxy {
     pub proxy_type: ProxyType,
     pub adapter: Box<dyn ProxyAdapter>,
+    /// 节点来源（订阅链接或本地文件），不参与比较和序列化
+    pub sources: Vec<String>,
 }
 
 impl Proxy {
@@ -141,6 +153,13 @@ impl Proxy {
         Proxy {
             proxy_type,
             adapter: proxy_adapter,
+            sources: Vec::new(),
+        }
+    }
+
+    pub fn add_source(&mut self, source: &str) {
+        if !self.sources.iter().any(|s| s == source) {
+            self.sources.push(source.to_string());
         }
     }
 
@@ -156,6 +175,10 @@ impl Proxy {
         self.adapter.get_server()
     }
 
+    pub fn get_port(&self) -> u16 {
+        self.adapter.get_port()
+    }
+
     pub fn to_json(&self) -> Result<String, serde_json::Error> {
         match self.adapter.to_json() {
             Ok(json) => {
@@ -306,6 +329,7 @@ impl Clone for Proxy {
         Proxy {
             proxy_type: self.proxy_type.clone(), // 确保 ProxyType 实现了 Clone
             adapter: self.adapter.clone(),       // 使用 adapter 的 clone_box 方法
+            sources: self.sources.clone(),
         }
     }
 }
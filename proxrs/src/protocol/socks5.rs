@@ -49,6 +49,10 @@ impl ProxyAdapter for Socks5 {
         &self.server
     }
 
+    fn get_port(&self) -> u16 {
+        self.port
+    }
+
     fn to_link(&self) -> String {
         todo!()
     }
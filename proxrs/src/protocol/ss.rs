@@ -10,6 +10,7 @@ use serde_json::Value;
 
 use crate::base64::base64decode;
 use crate::base64::base64encode;
+use crate::decode::decode_remark;
 use crate::protocol::deserialize_u16_or_string;
 use crate::protocol::ProxyAdapter;
 use crate::protocol::UnsupportedLinkError;
@@ -49,6 +50,10 @@ impl ProxyAdapter for SS {
         &self.server
     }
 
+    fn get_port(&self) -> u16 {
+        self.port
+    }
+
     /// 将节点信息转为单个分享链接
     /// https://github.com/v2rayA/v2rayA/blob/main/service/core/serverObj/shadowsocks.go#L354
     fn to_link(&self) -> String {
@@ -85,15 +90,13 @@ impl ProxyAdapter for SS {
     }
 
     fn from_link(link: String) -> Result<Self, UnsupportedLinkError> {
+        // SIP002 格式只有 userinfo 是 base64，整个链接解码失败时按明文继续解析
         let url = base64decode(&link[5..]);
         // parse name
         let mut name = String::from("");
         let parts: Vec<&str> = url.split("#").collect();
         if parts.len() > 1 {
-            name = urlencoding::decode(parts[1])
-                .unwrap_or_default()
-                .trim()
-                .to_string();
+            name = decode_remark(parts[1]).trim().to_string();
         }
 
         // parse plugin
@@ -135,6 +138,7 @@ impl ProxyAdapter for SS {
         let url = parts[0];
         let secret_server_port_parts: Vec<&str> = url.split("@").collect();
 
+        // userinfo 可能是 base64，也可能是百分号编码的明文 cipher:password
         let secret = base64decode(&urlencoding::decode(secret_server_port_parts[0]).unwrap());
         let cipher_pwd_parts: Vec<&str> = secret.splitn(2, ":").collect();
         let cipher = cipher_pwd_parts[0].parse().unwrap();
@@ -143,7 +147,10 @@ impl ProxyAdapter for SS {
         let server_port = secret_server_port_parts[1];
         let server_port_parts: Vec<&str> = server_port.split(":").collect();
         let server = server_port_parts[0].parse::<String>().unwrap();
-        let port = server_port_parts[1].trim_matches('/').parse::<u16>().unwrap();
+        let port = server_port_parts[1]
+            .trim_matches('/')
+            .parse::<u16>()
+            .unwrap();
 
         Ok(SS {
             name,
@@ -8,6 +8,7 @@ use serde::Serialize;
 use serde_json::Error;
 
 use crate::base64::base64decode;
+use crate::base64::try_base64decode_text;
 use crate::protocol::deserialize_u16_or_string;
 use crate::protocol::ProxyAdapter;
 use crate::protocol::UnsupportedLinkError;
@@ -47,6 +48,10 @@ impl ProxyAdapter for Ssr {
         &self.server
     }
 
+    fn get_port(&self) -> u16 {
+        self.port
+    }
+
     fn to_link(&self) -> String {
         todo!()
     }
@@ -55,13 +60,14 @@ impl ProxyAdapter for Ssr {
     where
         Self: Sized,
     {
-        let url = base64decode(&link[6..]);
+        let url = try_base64decode_text(&link[6..])?;
         let parts: Vec<&str> = url.split("/?").collect();
 
         let params = parts[1];
         let mut params_map: HashMap<&str, String> = HashMap::new();
         for param in params.split("&") {
             if let Some((key, value)) = param.split_once('=') {
+                // 部分订阅的 remarks 等参数直接使用明文
                 let value = base64decode(&value.parse::<String>().unwrap());
                 params_map.insert(key, value);
             }
@@ -74,7 +80,7 @@ impl ProxyAdapter for Ssr {
         let protocol = String::from(values[2]);
         let cipher = String::from(values[3]);
         let obfs = String::from(values[4]);
-        let password = base64decode(values[5]);
+        let password = try_base64decode_text(values[5])?;
 
         let mut name = String::from("");
         if let Some(result) = params_map.get("remarks") {
@@ -150,4 +156,12 @@ mod test {
         assert_eq!(ssr.protocol_param, Some("".to_string()));
         println!("{}", ssr.to_json().unwrap());
     }
+
+    #[test]
+    fn test_parse_ssr_invalid_base64() {
+        // 正文不是 base64 时返回错误，不把原文当作解码结果
+        let link =
+            String::from("ssr://vip.basicnode.host:11845:origin:aes-256-cfb:plain:pwd/?remarks=");
+        assert!(Ssr::from_link(link).is_err());
+    }
 }
@@ -7,6 +7,7 @@ use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Error;
 
+use crate::decode::decode_remark;
 use crate::protocol::deserialize_u16_or_string;
 use crate::protocol::ProxyAdapter;
 use crate::protocol::UnsupportedLinkError;
@@ -45,6 +46,10 @@ impl ProxyAdapter for Trojan {
         &self.server
     }
 
+    fn get_port(&self) -> u16 {
+        self.port
+    }
+
     fn to_link(&self) -> String {
         todo!()
     }
@@ -61,7 +66,7 @@ impl ProxyAdapter for Trojan {
         let mut name = String::from("");
         if let Some((v1, v2)) = url.rsplit_once("#") {
             url = v1;
-            name = urlencoding::decode(v2).unwrap().to_string();
+            name = decode_remark(v2);
         }
         // b7c0a9b4-0b85-4e93-921e-63bef702172b@111.38.53.159:41001
         // 4fee57cc-ee15-4800-888f-3493f7b261f2@hk1.ee2c9087-71b0-70af-7924-09d714b25b96.6df03129.
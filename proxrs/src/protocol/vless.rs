@@ -7,6 +7,7 @@ use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Error;
 
+use crate::decode::decode_remark;
 use crate::protocol::deserialize_u16_or_string;
 use crate::protocol::GrpcOptions;
 use crate::protocol::ProxyAdapter;
@@ -62,6 +63,10 @@ impl ProxyAdapter for Vless {
         &self.server
     }
 
+    fn get_port(&self) -> u16 {
+        self.port
+    }
+
     fn to_link(&self) -> String {
         todo!()
     }
@@ -74,7 +79,7 @@ impl ProxyAdapter for Vless {
         let parts = url.split("#").collect::<Vec<_>>();
         let mut name = "".to_string();
         if parts.len() > 1 {
-            name = urlencoding::decode(parts[1]).unwrap().to_string();
+            name = decode_remark(parts[1]);
         }
 
         let url = parts[0];
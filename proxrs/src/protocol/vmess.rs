@@ -9,6 +9,8 @@ use serde_json::Error;
 
 use crate::base64::base64decode;
 use crate::base64::base64encode;
+use crate::base64::try_base64decode_text;
+use crate::decode::decode_remark;
 use crate::protocol::deserialize_u16_or_string;
 use crate::protocol::GrpcOptions;
 use crate::protocol::ProxyAdapter;
@@ -92,6 +94,10 @@ impl ProxyAdapter for Vmess {
         &self.server
     }
 
+    fn get_port(&self) -> u16 {
+        self.port
+    }
+
     fn to_link(&self) -> String {
         let mut host = None;
         let mut path = None;
@@ -136,6 +142,7 @@ impl ProxyAdapter for Vmess {
     where
         Self: Sized,
     {
+        // Shadowrocket 格式的正文后带有明文参数，整体不是 base64，原样交给下面的分支解析
         let url = base64decode(&link[8..]);
         match serde_json::from_str::<serde_json::Value>(&url) {
             Ok(parsed) => {
@@ -232,14 +239,13 @@ impl ProxyAdapter for Vmess {
                     }
                 }
                 let alter_id = params_map.get("alterId").unwrap().parse::<u16>().unwrap();
-                let name = urlencoding::decode(params_map.get("remarks").unwrap())
-                    .unwrap()
-                    .to_string();
+                let name = decode_remark(params_map.get("remarks").unwrap());
 
                 // parse server port
-                let url = base64decode(parts[0]);
+                let url = try_base64decode_text(parts[0])?;
                 let secret_server_port_parts: Vec<&str> = url.split("@").collect();
 
+                // 解码后的 cipher:uuid 通常已是明文
                 let secret = base64decode(secret_server_port_parts[0]);
                 let cipher_pwd_parts: Vec<&str> = secret.splitn(2, ":").collect();
                 let cipher = cipher_pwd_parts[0].parse().unwrap();
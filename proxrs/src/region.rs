@@ -0,0 +1,344 @@
+/// 地区信息，code 为 ISO 3166-1 alpha-2 国家代码
+pub struct Region {
+    pub code: &'static str,
+    pub name: &'static str,
+    // 中文、日文名称和城市，按子串匹配，单字简称需前后都不是文字，避免「港口」之类的误匹配
+    cjk: &'static [&'static str],
+    // 英文名称和城市，按完整单词匹配，不区分大小写
+    words: &'static [&'static str],
+    // 机场代码等大写缩写，按完整单词匹配，区分大小写
+    codes: &'static [&'static str],
+}
+
+// 不包含中国大陆，节点名中的「中国」多为「中国香港」或中转线路说明
+pub const REGIONS: &[Region] = &[
+    Region {
+        code: "HK",
+        name: "Hong Kong",
+        cjk: &["香港", "港"],
+        words: &["hong kong", "hongkong"],
+        codes: &["HK", "HKG", "HKT", "HKBN"],
+    },
+    Region {
+        code: "TW",
+        name: "Taiwan",
+        cjk: &[
+            "台湾", "台灣", "臺灣", "台北", "臺北", "新北", "彰化", "台中", "高雄",
+        ],
+        words: &["taiwan", "taipei", "hinet"],
+        codes: &["TW", "TPE", "KHH", "CHT"],
+    },
+    Region {
+        code: "MO",
+        name: "Macao",
+        cjk: &["澳门", "澳門"],
+        words: &["macao", "macau"],
+        codes: &["MO", "MFM"],
+    },
+    Region {
+        code: "JP",
+        name: "Japan",
+        cjk: &[
+            "日本",
+            "东京",
+            "東京",
+            "大阪",
+            "埼玉",
+            "名古屋",
+            "横滨",
+            "横浜",
+        ],
+        words: &["japan", "tokyo", "osaka", "saitama", "nagoya", "yokohama"],
+        codes: &["JP", "NRT", "HND", "KIX", "ITM", "NGO"],
+    },
+    Region {
+        code: "KR",
+        name: "South Korea",
+        cjk: &[
+            "韩国", "韓国", "韓國", "首尔", "首爾", "春川", "한국", "서울",
+        ],
+        words: &["korea", "seoul", "chuncheon"],
+        codes: &["KR", "ICN", "GMP"],
+    },
+    Region {
+        code: "SG",
+        name: "Singapore",
+        cjk: &["新加坡", "狮城", "獅城", "シンガポール"],
+        words: &["singapore"],
+        codes: &["SG", "SIN"],
+    },
+    Region {
+        code: "US",
+        name: "United States",
+        cjk: &[
+            "美国",
+            "美國",
+            "洛杉矶",
+            "洛杉磯",
+            "硅谷",
+            "矽谷",
+            "圣何塞",
+            "聖何塞",
+            "西雅图",
+            "芝加哥",
+            "纽约",
+            "紐約",
+            "达拉斯",
+            "凤凰城",
+            "波特兰",
+            "迈阿密",
+            "亚特兰大",
+            "アメリカ",
+            "米国",
+        ],
+        words: &[
+            "united states",
+            "america",
+            "usa",
+            "los angeles",
+            "san jose",
+            "silicon valley",
+            "seattle",
+            "chicago",
+            "new york",
+            "dallas",
+            "phoenix",
+            "portland",
+            "miami",
+            "atlanta",
+            "ashburn",
+        ],
+        codes: &[
+            "US", "LAX", "SJC", "SEA", "ORD", "JFK", "DFW", "PHX", "MIA", "ATL", "IAD",
+        ],
+    },
+    Region {
+        code: "CA",
+        name: "Canada",
+        cjk: &["加拿大", "多伦多", "温哥华", "蒙特利尔"],
+        words: &["canada", "toronto", "vancouver", "montreal"],
+        codes: &["CA", "YYZ", "YVR", "YUL"],
+    },
+    Region {
+        code: "GB",
+        name: "United Kingdom",
+        cjk: &["英国", "英國", "伦敦", "倫敦", "イギリス"],
+        words: &["united kingdom", "britain", "england", "london"],
+        codes: &["GB", "UK", "LHR", "LGW"],
+    },
+    Region {
+        code: "DE",
+        name: "Germany",
+        cjk: &["德国", "德國", "法兰克福", "柏林", "ドイツ"],
+        words: &["germany", "frankfurt", "berlin", "nuremberg", "falkenstein"],
+        codes: &["DE", "FRA", "BER"],
+    },
+    Region {
+        code: "FR",
+        name: "France",
+        cjk: &["法国", "法國", "巴黎", "フランス"],
+        words: &["france", "paris", "marseille"],
+        codes: &["FR", "CDG"],
+    },
+    Region {
+        code: "NL",
+        name: "Netherlands",
+        cjk: &["荷兰", "荷蘭", "阿姆斯特丹", "オランダ"],
+        words: &["netherlands", "holland", "amsterdam"],
+        codes: &["NL", "AMS"],
+    },
+    Region {
+        code: "RU",
+        name: "Russia",
+        cjk: &["俄罗斯", "俄羅斯", "莫斯科", "伯力", "ロシア"],
+        words: &["russia", "moscow", "khabarovsk"],
+        codes: &["RU", "SVO", "DME"],
+    },
+    Region {
+        code: "TR",
+        name: "Turkey",
+        cjk: &["土耳其", "伊斯坦布尔"],
+        words: &["turkey", "turkiye", "istanbul"],
+        codes: &["TR", "IST"],
+    },
+    Region {
+        code: "IN",
+        name: "India",
+        cjk: &["印度", "孟买", "インド"],
+        words: &["india", "mumbai", "bangalore"],
+        codes: &["BOM", "DEL"],
+    },
+    Region {
+        code: "AU",
+        name: "Australia",
+        cjk: &["澳大利亚", "澳洲", "悉尼", "墨尔本"],
+        words: &["australia", "sydney", "melbourne"],
+        codes: &["AU", "SYD", "MEL"],
+    },
+    Region {
+        code: "MY",
+        name: "Malaysia",
+        cjk: &["马来西亚", "馬來西亞", "吉隆坡"],
+        words: &["malaysia", "kuala lumpur"],
+        codes: &["MY", "KUL"],
+    },
+    Region {
+        code: "TH",
+        name: "Thailand",
+        cjk: &["泰国", "泰國", "曼谷"],
+        words: &["thailand", "bangkok"],
+        codes: &["TH", "BKK"],
+    },
+    Region {
+        code: "VN",
+        name: "Vietnam",
+        cjk: &["越南", "河内", "胡志明"],
+        words: &["vietnam", "hanoi"],
+        codes: &["VN", "SGN", "HAN"],
+    },
+    Region {
+        code: "PH",
+        name: "Philippines",
+        cjk: &["菲律宾", "菲律賓", "马尼拉"],
+        words: &["philippines", "manila"],
+        codes: &["PH", "MNL"],
+    },
+    Region {
+        code: "ID",
+        name: "Indonesia",
+        cjk: &["印尼", "印度尼西亚", "雅加达"],
+        words: &["indonesia", "jakarta"],
+        codes: &["CGK"],
+    },
+    Region {
+        code: "AE",
+        name: "United Arab Emirates",
+        cjk: &["阿联酋", "迪拜"],
+        words: &["emirates", "dubai"],
+        codes: &["AE", "UAE", "DXB"],
+    },
+    Region {
+        code: "BR",
+        name: "Brazil",
+        cjk: &["巴西", "圣保罗"],
+        words: &["brazil", "sao paulo"],
+        codes: &["BR", "GRU"],
+    },
+    Region {
+        code: "AR",
+        name: "Argentina",
+        cjk: &["阿根廷"],
+        words: &["argentina", "buenos aires"],
+        codes: &["AR", "EZE"],
+    },
+];
+
+/// 按国家代码查找地区
+pub fn find_region(code: &str) -> Option<&'static Region> {
+    REGIONS.iter().find(|r| r.code.eq_ignore_ascii_case(code))
+}
+
+/// 从节点名称推断国家代码，依次尝试国旗 emoji、中日文名称、英文名称和城市、国家代码和机场代码
+/// 国旗可以识别任意国家，其余方式仅识别 REGIONS 中的地区
+pub fn infer_country_code(name: &str) -> Option<String> {
+    if let Some(code) = flag_country_code(name) {
+        return Some(code);
+    }
+    // 同一名称命中多个地区时取最先出现的，如「香港-日本」视为香港
+    let cjk = REGIONS
+        .iter()
+        .flat_map(|r| r.cjk.iter().map(move |k| (r.code, *k)))
+        .filter_map(|(code, keyword)| find_cjk(name, keyword).map(|pos| (pos, code)))
+        .min_by_key(|(pos, _)| *pos);
+    if let Some((_, code)) = cjk {
+        return Some(code.to_string());
+    }
+
+    let tokens: Vec<&str> = name
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .filter(|t| !t.is_empty())
+        .collect();
+    let words = format!(" {} ", tokens.join(" ").to_lowercase());
+    let english = REGIONS
+        .iter()
+        .flat_map(|r| r.words.iter().map(move |w| (r.code, *w)))
+        .filter_map(|(code, word)| words.find(&format!(" {} ", word)).map(|pos| (pos, code)))
+        .min_by_key(|(pos, _)| *pos);
+    if let Some((_, code)) = english {
+        return Some(code.to_string());
+    }
+
+    tokens.iter().find_map(|token| {
+        REGIONS
+            .iter()
+            .find(|r| r.codes.contains(token))
+            .map(|r| r.code.to_string())
+    })
+}
+
+fn find_cjk(name: &str, keyword: &str) -> Option<usize> {
+    if keyword.chars().count() > 1 {
+        return name.find(keyword);
+    }
+    let standalone = |c: Option<char>| c.is_none_or(|c| !c.is_alphabetic());
+    name.match_indices(keyword)
+        .find(|(pos, _)| {
+            standalone(name[..*pos].chars().next_back())
+                && standalone(name[pos + keyword.len()..].chars().next())
+        })
+        .map(|(pos, _)| pos)
+}
+
+// 国旗 emoji 由两个区域指示符组成，如 🇭🇰 对应 HK
+fn flag_country_code(name: &str) -> Option<String> {
+    let indicator = |c: char| {
+        let c = c as u32;
+        (0x1F1E6..=0x1F1FF)
+            .contains(&c)
+            .then(|| char::from(b'A' + (c - 0x1F1E6) as u8))
+    };
+    let chars: Vec<char> = name.chars().collect();
+    chars.windows(2).find_map(|pair| {
+        let first = indicator(pair[0])?;
+        let second = indicator(pair[1])?;
+        Some(format!("{}{}", first, second))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_country_code() {
+        let cases = [
+            ("🇭🇰香港 01 | 专线", Some("HK")),
+            ("🇹🇼 Taiwan 02", Some("TW")),
+            ("🇩🇪 Frankfurt", Some("DE")),
+            ("香港-日本 IPLC", Some("HK")),
+            ("東京 03", Some("JP")),
+            ("シンガポール 01", Some("SG")),
+            ("Los Angeles 05", Some("US")),
+            ("hong kong premium", Some("HK")),
+            ("HK01", Some("HK")),
+            ("LAX-CN2-GIA", Some("US")),
+            ("Relay_NRT_x2", Some("JP")),
+            ("港 01", Some("HK")),
+            ("港口 01", None),
+            ("高雄港 IEPL", Some("TW")),
+            ("深港专线 02", None),
+            ("Plus 1", None),
+            ("Russian roulette", None),
+            ("免费节点", None),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(infer_country_code(name).as_deref(), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_find_region() {
+        assert_eq!(find_region("hk").unwrap().name, "Hong Kong");
+        assert!(find_region("XX").is_none());
+    }
+}
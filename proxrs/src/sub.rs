@@ -1,25 +1,153 @@
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fs;
 use std::fs::File;
+use std::future::Future;
 use std::hash::DefaultHasher;
 use std::hash::Hash;
 use std::hash::Hasher;
 use std::io;
 use std::io::Read;
 use std::io::Write;
+use std::net::IpAddr;
 use std::path::Path;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::LazyLock;
 use std::time::Duration;
 
 use regex::Regex;
+use reqwest::header::CONTENT_ENCODING;
 use reqwest::Client;
+use serde::Deserialize;
+use serde::Serialize;
 use serde_yaml::Mapping;
 use serde_yaml::Value;
+use sha2::Digest;
+use sha2::Sha256;
+use tokio::net::lookup_host;
+use tokio::task::JoinSet;
 use tokio::time::sleep;
+use tokio::time::timeout;
+use tracing::debug;
+use tracing::error;
 use tracing::info;
+use tracing::warn;
 
-use crate::base64::base64decode;
+use crate::decode::decode_base64_content;
+use crate::decode::decode_bytes;
+use crate::decode::Decoding;
 use crate::protocol::Proxy;
+use crate::region;
+
+/// proxy-providers 嵌套解析的最大深度，根订阅深度为 0
+const MAX_PROVIDER_DEPTH: usize = 3;
+
+/// 严格去重时忽略的字段，这些字段不改变实际连接的目标
+const NON_IDENTITY_FIELDS: &[&str] = &[
+    "name",
+    "udp",
+    "tfo",
+    "skip-cert-verify",
+    "fingerprint",
+    "client-fingerprint",
+];
+
+/// 去重时解析单个域名的超时时间
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(3);
+
+// 机场放在订阅中展示流量、到期时间等信息的伪节点
+static TRAFFIC_INFO: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(?:剩余流量|流量剩余|剩余|traffic|remaining)\s*[:：]?\s*([\d.]+\s*[KMGTP]i?B)")
+        .unwrap()
+});
+static EXPIRE_INFO: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(?:套餐到期|到期时间|过期时间|到期|过期|expire[sd]?)\s*[:：]?\s*(.*)").unwrap()
+});
+static RESET_INFO: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:距离下次重置剩余|距离下次重置|下次重置|重置)\s*[:：]?\s*(.*)").unwrap()
+});
+static WEBSITE_INFO: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:官网|官方网站|网址|订阅地址)\s*[:：]?\s*(.*)").unwrap());
+// 频道、建议等常见词也会出现在正常节点名称中，只在名称开头时视为公告
+static NOTICE_INFO: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)公告|客服|交流群|TG群|^[^\p{L}\p{N}]*(?:频道|群组|telegram|请勿|建议|使用前|如遇)",
+    )
+    .unwrap()
+});
+
+type ProxiesFuture<'a> = Pin<Box<dyn Future<Output = Vec<Proxy>> + 'a>>;
+
+/// Clash 配置中 proxy-providers 的一项，仅支持 http 和 file 两种类型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProxyProvider {
+    Http { name: String, url: String },
+    File { name: String, path: String },
+}
+
+/// 节点去重策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupPolicy {
+    /// 完整配置一致才视为重复，包含传输层、TLS、REALITY 等参数
+    #[default]
+    Strict,
+    /// 仅比较协议、服务器和端口
+    Loose,
+    /// 与 strict 相同，但服务器地址先解析为 IP 再比较
+    ResolvedIp,
+}
+
+/// 解析多个订阅来源的结果
+#[derive(Debug, Default)]
+pub struct ParsedSubs {
+    pub proxies: Vec<Proxy>,
+    // 按来源汇总的信息节点内容，来源为目录或 glob 时记录到具体的文件
+    pub infos: BTreeMap<String, SourceInfo>,
+}
+
+/// 解析单个来源及其 proxy-providers 时共享的状态
+#[derive(Debug, Default)]
+struct ResolveContext {
+    // 已访问的链接和文件，用于检测 provider 之间的循环引用
+    visited: HashSet<String>,
+    ad_phrases: Vec<String>,
+    // 从信息节点中提取的订阅信息
+    info: SourceInfo,
+}
+
+/// 伪节点中携带的订阅信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InfoNode {
+    Traffic(String),
+    Expire(String),
+    Reset(String),
+    Website(String),
+    Notice,
+}
+
+/// 从伪节点中提取的订阅来源信息
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceInfo {
+    pub remaining_traffic: Option<String>,
+    pub expire: Option<String>,
+    pub reset: Option<String>,
+    pub website: Option<String>,
+}
+
+impl SourceInfo {
+    fn merge(&mut self, info: InfoNode) {
+        match info {
+            InfoNode::Traffic(v) => self.remaining_traffic = Some(v),
+            InfoNode::Expire(v) => self.expire = Some(v),
+            InfoNode::Reset(v) => self.reset = Some(v),
+            InfoNode::Website(v) => self.website = Some(v),
+            InfoNode::Notice => {}
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct SubManager {}
@@ -30,34 +158,235 @@ impl SubManager {
     /// 2. C:\\文件地址 /home/yaml，传入文件地址
     /// 3. ss://xxxx，传入单个节点链接
     /// 4. edhxxx, 传入 base64 的节点信息
+    /// 5. ./subs/raw 或 ./subs/raw/*.yaml，传入目录或 glob，逐个解析其中的文件
+    ///
+    /// 若解析出的 Clash 配置中声明了 proxy-providers，会递归拉取其中的节点并合并
+    /// 解析出的节点会记录来源，目录和 glob 会记录到具体的文件
     pub async fn get_proxies_from_url(url: String) -> Vec<Proxy> {
-        let mut proxies: Vec<Proxy> = Vec::new();
-        if url.starts_with("http") {
-            if let Ok(file_path) = Self::get_content_from_sub_url(&url).await {
-                proxies = Self::parse_content(file_path).unwrap();
+        Self::parse_url(&url, &[]).await.proxies
+    }
+
+    async fn parse_url(url: &str, ad_phrases: &[String]) -> ParsedSubs {
+        let sources = match Self::expand_local_sources(url) {
+            Some(files) => {
+                info!("{} matched files: {}", url, files.len());
+                files
+            }
+            None => vec![url.to_string()],
+        };
+        let mut parsed = ParsedSubs::default();
+        for source in sources {
+            let (proxies, info) = Self::get_proxies_from_source(source.clone(), ad_phrases).await;
+            parsed.proxies.extend(proxies);
+            if info != SourceInfo::default() {
+                parsed.infos.insert(source, info);
             }
-        } else if Path::new(&url).is_file() {
-            proxies = Self::parse_from_path(&url).unwrap();
-        } else if let Ok(p) = Self::parse_content(url.to_string()) {
-            proxies.extend(p);
         }
-        info!("{} parsed proxies: {}", &url, &proxies.len());
-        proxies
+        parsed
+    }
+
+    async fn get_proxies_from_source(
+        source: String,
+        ad_phrases: &[String],
+    ) -> (Vec<Proxy>, SourceInfo) {
+        let mut context = ResolveContext {
+            ad_phrases: ad_phrases.to_vec(),
+            ..Default::default()
+        };
+        let mut proxies = Self::resolve_source(source.clone(), None, 0, &mut context).await;
+        for proxy in &mut proxies {
+            proxy.add_source(&source);
+        }
+        info!("{} parsed proxies: {}", &source, &proxies.len());
+        (proxies, context.info)
     }
 
-    /// 传入 urls 列表解析代理
-    pub async fn get_proxies_from_urls(subs: &Vec<String>) -> Vec<Proxy> {
-        let mut proxies: Vec<Proxy> = Vec::new();
+    /// 将目录或 glob 展开为其中的文件列表，其它类型的来源返回 None
+    /// 目录只读取第一层，忽略隐藏文件
+    pub fn expand_local_sources(url: &str) -> Option<Vec<String>> {
+        if url.starts_with("http") || url.contains("://") {
+            return None;
+        }
+        let mut files: Vec<PathBuf> = if Path::new(url).is_dir() {
+            fs::read_dir(url)
+                .ok()?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| {
+                    path.is_file()
+                        && !path
+                            .file_name()
+                            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+                })
+                .collect()
+        } else if url.contains(['*', '?', '[']) {
+            match glob::glob(url) {
+                Ok(paths) => paths
+                    .filter_map(Result::ok)
+                    .filter(|p| p.is_file())
+                    .collect(),
+                Err(e) => {
+                    error!("无效的 glob 表达式 {}, {}", url, e);
+                    return None;
+                }
+            }
+        } else {
+            return None;
+        };
+        files.sort();
+        Some(
+            files
+                .into_iter()
+                .map(|path| path.to_string_lossy().to_string())
+                .collect(),
+        )
+    }
+
+    /// 传入 urls 列表解析代理，并按 policy 去重
+    /// 每个来源解析后先移除信息节点并清理名称中的 ad_phrases，再去重和处理重名
+    pub async fn get_proxies_from_urls(
+        subs: &Vec<String>,
+        policy: DedupPolicy,
+        ad_phrases: &[String],
+    ) -> ParsedSubs {
+        let mut parsed = ParsedSubs::default();
         for url in subs {
-            proxies.extend(Self::get_proxies_from_url(url.to_string()).await)
+            let result = Self::parse_url(url, ad_phrases).await;
+            parsed.proxies.extend(result.proxies);
+            parsed.infos.extend(result.infos);
         }
 
-        if !proxies.is_empty() {
-            proxies = Self::exclude_dup_proxies(proxies);
-            Self::rename_dup_proxies_name(&mut proxies);
+        if !parsed.proxies.is_empty() {
+            parsed.proxies = Self::exclude_dup_proxies(parsed.proxies, policy).await;
+            Self::rename_dup_proxies_name(&mut parsed.proxies);
         }
 
-        proxies
+        parsed
+    }
+
+    /// 解析单个来源，并跟随其中的 proxy-providers
+    /// base_dir 为父配置所在目录，用于解析 file 类型 provider 的相对路径
+    fn resolve_source<'a>(
+        source: String,
+        base_dir: Option<PathBuf>,
+        depth: usize,
+        context: &'a mut ResolveContext,
+    ) -> ProxiesFuture<'a> {
+        Box::pin(async move {
+            let mut proxies: Vec<Proxy> = Vec::new();
+            let content;
+            let mut source_dir = None;
+            if source.starts_with("http") {
+                if !context.visited.insert(source.clone()) {
+                    info!("{} 已解析过，跳过循环引用", &source);
+                    return proxies;
+                }
+                match Self::get_content_from_sub_url(&source).await {
+                    Ok(c) => content = c,
+                    Err(e) => {
+                        error!("{}", e);
+                        return proxies;
+                    }
+                }
+            } else {
+                let path = match &base_dir {
+                    Some(dir) if Path::new(&source).is_relative() => dir.join(&source),
+                    _ => PathBuf::from(&source),
+                };
+                if path.is_file() {
+                    let key = fs::canonicalize(&path).unwrap_or(path.clone());
+                    if !context.visited.insert(key.to_string_lossy().to_string()) {
+                        info!("{} 已解析过，跳过循环引用", &source);
+                        return proxies;
+                    }
+                    match Self::read_content_from_path(&path) {
+                        Ok(c) => content = c,
+                        Err(e) => {
+                            error!("{}", e);
+                            return proxies;
+                        }
+                    }
+                    source_dir = key.parent().map(Path::to_path_buf);
+                } else if base_dir.is_some() {
+                    // provider 的 path 只能是文件，文件不存在时不能当作节点内容解析
+                    error!("provider 文件 {} 不存在，已跳过", path.to_string_lossy());
+                    return proxies;
+                } else {
+                    // 单个节点链接或 base64 内容，不会包含 provider
+                    match Self::parse_content(source.clone()) {
+                        Ok(p) if !p.is_empty() => proxies.extend(p),
+                        _ => {
+                            let preview: String = source.chars().take(64).collect();
+                            warn!("{} 不是存在的文件，也不是有效的节点内容，已跳过", preview);
+                            return proxies;
+                        }
+                    }
+                    Self::clean_source_proxies(&mut proxies, context);
+                    return proxies;
+                }
+            }
+
+            match Self::parse_content(content.clone()) {
+                Ok(p) => proxies.extend(p),
+                Err(e) => {
+                    warn!("{} 解析失败，已跳过, {}", &source, e);
+                    return proxies;
+                }
+            }
+            Self::clean_source_proxies(&mut proxies, context);
+
+            for provider in Self::parse_proxy_providers(&content) {
+                if depth >= MAX_PROVIDER_DEPTH {
+                    error!(
+                        "proxy-providers 嵌套超过 {} 层，已忽略 {:?}",
+                        MAX_PROVIDER_DEPTH, provider
+                    );
+                    continue;
+                }
+                let (name, child) = match provider {
+                    ProxyProvider::Http { name, url } => (name, url),
+                    ProxyProvider::File { name, path } => (name, path),
+                };
+                // 父配置来自订阅链接时，file 类型 provider 的相对路径基于当前目录
+                let child_dir = source_dir.clone().or_else(|| base_dir.clone());
+                let child_proxies = Self::resolve_source(
+                    child,
+                    Some(child_dir.unwrap_or_default()),
+                    depth + 1,
+                    context,
+                )
+                .await;
+                info!("provider {} parsed proxies: {}", name, child_proxies.len());
+                proxies.extend(child_proxies);
+            }
+            proxies
+        })
+    }
+
+    /// 读取 Clash 配置中的 proxy-providers，非 Clash 配置或未声明时返回空
+    pub fn parse_proxy_providers(content: &str) -> Vec<ProxyProvider> {
+        let mut providers = Vec::new();
+        let Ok(yaml) = serde_yaml::from_str::<serde_json::Value>(content) else {
+            return providers;
+        };
+        let Some(map) = yaml.get("proxy-providers").and_then(|p| p.as_object()) else {
+            return providers;
+        };
+        for (name, provider) in map {
+            let provider_type = provider.get("type").and_then(|t| t.as_str());
+            let field = |key: &str| provider.get(key).and_then(|v| v.as_str()).map(String::from);
+            match (provider_type, field("url"), field("path")) {
+                (Some("http"), Some(url), _) => providers.push(ProxyProvider::Http {
+                    name: name.clone(),
+                    url,
+                }),
+                (Some("file"), _, Some(path)) => providers.push(ProxyProvider::File {
+                    name: name.clone(),
+                    path,
+                }),
+                _ => info!("不支持的 proxy-provider {}: {}", name, provider),
+            }
+        }
+        providers
     }
 
     async fn get_content_from_sub_url(sub_url: &str) -> Result<String, Box<dyn std::error::Error>> {
@@ -86,13 +415,22 @@ impl SubManager {
                         // let file_path = PathBuf::from_iter(vec!["subs", &uuid.to_string()]);
                         // let mut file = File::create(&file_path).unwrap();
 
-                        let content_result = resp.text().await;
+                        let content_encoding = resp
+                            .headers()
+                            .get(CONTENT_ENCODING)
+                            .and_then(|v| v.to_str().ok())
+                            .map(String::from);
+                        let content_result = resp.bytes().await;
                         match content_result {
-                            Ok(content) => {
+                            Ok(bytes) => {
                                 // file.write_all(content.as_bytes()).unwrap();
                                 // Ok(env::current_dir().unwrap().join(file_path).to_string_lossy().
                                 // to_string())
-                                Ok(content)
+                                let decoded = decode_bytes(&bytes, content_encoding.as_deref())?;
+                                if !decoded.decodings.is_empty() {
+                                    info!("{} 解码方式: {:?}", sub_url, decoded.decodings);
+                                }
+                                Ok(decoded.content)
                             }
                             Err(e) => {
                                 if e.is_timeout() {
@@ -129,10 +467,20 @@ impl SubManager {
     pub fn parse_from_path<P: AsRef<Path>>(
         file_path: P,
     ) -> Result<Vec<Proxy>, Box<dyn std::error::Error>> {
-        match fs::read_to_string(file_path) {
-            Ok(contents) => Ok(Self::parse_content(contents)?),
-            Err(e) => Err(format!("Error reading file: {}", e).into()),
+        Self::parse_content(Self::read_content_from_path(file_path)?)
+    }
+
+    /// 读取本地文件并解码，兼容压缩文件、BOM 和 GBK 编码
+    fn read_content_from_path<P: AsRef<Path>>(
+        file_path: P,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let path = file_path.as_ref();
+        let bytes = fs::read(path).map_err(|e| format!("Error reading file: {}", e))?;
+        let decoded = decode_bytes(&bytes, None)?;
+        if !decoded.decodings.is_empty() {
+            info!("{} 解码方式: {:?}", path.display(), decoded.decodings);
         }
+        Ok(decoded.content)
     }
 
     /// 从字符串中解析代理
@@ -159,6 +507,8 @@ impl SubManager {
         let mut conf_proxies: Vec<Proxy> = Vec::new();
         let yaml = serde_yaml::from_str::<serde_json::Value>(content)?;
         match yaml.get("proxies").or_else(|| yaml.get("Proxies")) {
+            // 仅声明了 proxy-providers 的配置，节点由 resolve_source 拉取
+            None if yaml.get("proxy-providers").is_some() => {}
             None => {
                 return Err(format!("Proxy not found: {}", content).into());
             }
@@ -183,8 +533,10 @@ impl SubManager {
 
     fn parse_base64_content(content: &str) -> Result<Vec<Proxy>, Box<dyn std::error::Error>> {
         let mut conf_proxies: Vec<Proxy> = Vec::new();
-        let base64 = base64decode(content.trim());
-        base64
+        let decoded = decode_base64_content(content)?;
+        debug!("base64 解码方式: {:?}", decoded.decodings);
+        decoded
+            .content
             .split("\n")
             .filter(|line| !line.is_empty())
             .for_each(|line| match Proxy::from_link(line.trim().to_string()) {
@@ -193,6 +545,10 @@ impl SubManager {
                     println!("{}", e);
                 }
             });
+        // 任意字节都可能被 GBK 解码，解不出节点时视为不是 base64 订阅
+        if conf_proxies.is_empty() && decoded.decodings.contains(&Decoding::Gbk) {
+            return Err("GBK 解码后没有有效节点".into());
+        }
         Ok(conf_proxies)
     }
 
@@ -211,17 +567,205 @@ impl SubManager {
         Ok(conf_proxies)
     }
 
-    /// 移除重复节点
-    pub fn exclude_dup_proxies(proxies: Vec<Proxy>) -> Vec<Proxy> {
-        let mut new_proxies = Vec::new();
-        if !proxies.is_empty() {
-            let set: HashSet<Proxy> = HashSet::from_iter(proxies);
-            new_proxies = set.into_iter().collect();
-            new_proxies.sort_by(|a, b| a.proxy_type.cmp(&b.proxy_type));
+    /// 按 policy 移除重复节点
+    /// 重复的节点中保留字段最完整的一个，并合并所有重复节点的来源
+    pub async fn exclude_dup_proxies(proxies: Vec<Proxy>, policy: DedupPolicy) -> Vec<Proxy> {
+        let resolved = match policy {
+            DedupPolicy::ResolvedIp => Self::resolve_servers(&proxies).await,
+            _ => HashMap::new(),
+        };
+        let mut new_proxies: Vec<Proxy> = Vec::new();
+        let mut indexes: HashMap<String, usize> = HashMap::new();
+        for proxy in proxies {
+            let key = Self::dedup_key(&proxy, policy, &resolved);
+            match indexes.get(&key) {
+                Some(&index) => {
+                    let mut sources = std::mem::take(&mut new_proxies[index].sources);
+                    if Self::completeness(&proxy) > Self::completeness(&new_proxies[index]) {
+                        new_proxies[index] = proxy.clone();
+                    }
+                    for source in &proxy.sources {
+                        if !sources.contains(source) {
+                            sources.push(source.clone());
+                        }
+                    }
+                    new_proxies[index].sources = sources;
+                }
+                None => {
+                    indexes.insert(key, new_proxies.len());
+                    new_proxies.push(proxy);
+                }
+            }
         }
+        new_proxies.sort_by(|a, b| a.proxy_type.cmp(&b.proxy_type));
         new_proxies
     }
 
+    /// 计算节点在 policy 下的去重标识
+    fn dedup_key(proxy: &Proxy, policy: DedupPolicy, resolved: &HashMap<String, IpAddr>) -> String {
+        match policy {
+            DedupPolicy::Loose => {
+                format!(
+                    "{:?}|{}|{}",
+                    proxy.proxy_type,
+                    proxy.get_server(),
+                    proxy.get_port()
+                )
+            }
+            DedupPolicy::Strict | DedupPolicy::ResolvedIp => {
+                let mut identity = Self::identity(proxy);
+                if let Some(ip) = resolved.get(proxy.get_server()) {
+                    identity.insert("server".to_string(), serde_json::json!(ip.to_string()));
+                }
+                serde_json::Value::Object(identity).to_string()
+            }
+        }
+    }
+
+    /// 节点内容指纹，与名称等不影响连接目标的字段无关，可用于跨多次运行识别同一节点
+    pub fn fingerprint(proxy: &Proxy) -> String {
+        let identity = serde_json::Value::Object(Self::identity(proxy)).to_string();
+        Sha256::digest(identity.as_bytes())[..8]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// 节点的完整配置去掉名称和不影响连接目标的字段，包含传输层和 TLS 参数
+    fn identity(proxy: &Proxy) -> serde_json::Map<String, serde_json::Value> {
+        let mut identity = proxy
+            .to_json()
+            .ok()
+            .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok())
+            .and_then(|value| value.as_object().cloned())
+            .unwrap_or_default();
+        for field in NON_IDENTITY_FIELDS {
+            identity.remove(*field);
+        }
+        identity
+    }
+
+    /// 节点配置中非空字段的个数，用于在重复节点中挑选信息最全的一个
+    fn completeness(proxy: &Proxy) -> usize {
+        fn count(value: &serde_json::Value) -> usize {
+            match value {
+                serde_json::Value::Null => 0,
+                serde_json::Value::Object(map) => map.values().map(count).sum(),
+                serde_json::Value::Array(arr) => arr.iter().map(count).sum(),
+                _ => 1,
+            }
+        }
+        proxy
+            .to_json()
+            .ok()
+            .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok())
+            .map_or(0, |value| count(&value))
+    }
+
+    /// 解析所有域名类型的服务器地址，解析失败的保持原样参与去重
+    async fn resolve_servers(proxies: &[Proxy]) -> HashMap<String, IpAddr> {
+        let domains: HashSet<(String, u16)> = proxies
+            .iter()
+            .filter(|p| p.get_server().parse::<IpAddr>().is_err())
+            .map(|p| (p.get_server().to_string(), p.get_port()))
+            .collect();
+        let mut tasks = JoinSet::new();
+        for (domain, port) in domains {
+            tasks.spawn(async move {
+                let result = timeout(RESOLVE_TIMEOUT, lookup_host((domain.clone(), port))).await;
+                let ip = match result {
+                    Ok(Ok(mut addrs)) => addrs.next().map(|addr| addr.ip()),
+                    _ => None,
+                };
+                (domain, ip)
+            });
+        }
+        let mut resolved = HashMap::new();
+        while let Some(result) = tasks.join_next().await {
+            if let Ok((domain, Some(ip))) = result {
+                resolved.insert(domain, ip);
+            }
+        }
+        info!("resolved servers: {}", resolved.len());
+        resolved
+    }
+
+    /// 判断节点是否为展示流量、到期时间、官网等信息的伪节点，并提取其中的信息
+    pub fn classify_info_node(name: &str) -> Option<InfoNode> {
+        let value = |re: &Regex| {
+            re.captures(name)
+                .map(|c| c.get(1).map_or("", |m| m.as_str()).trim().to_string())
+        };
+        if let Some(traffic) = value(&TRAFFIC_INFO) {
+            return Some(InfoNode::Traffic(traffic));
+        }
+        if let Some(expire) = value(&EXPIRE_INFO) {
+            return Some(InfoNode::Expire(expire));
+        }
+        if let Some(reset) = value(&RESET_INFO) {
+            return Some(InfoNode::Reset(reset));
+        }
+        if let Some(website) = value(&WEBSITE_INFO) {
+            return Some(InfoNode::Website(website));
+        }
+        if NOTICE_INFO.is_match(name) {
+            return Some(InfoNode::Notice);
+        }
+        None
+    }
+
+    /// 移除信息伪节点，其中的订阅信息合并到 info
+    pub fn strip_info_proxies(proxies: &mut Vec<Proxy>, info: &mut SourceInfo) {
+        proxies.retain(|proxy| match Self::classify_info_node(proxy.get_name()) {
+            Some(node) => {
+                info!("移除信息节点「{}」", proxy.get_name());
+                info.merge(node);
+                false
+            }
+            None => true,
+        });
+    }
+
+    // 在去重和处理重名之前执行，此时名称中的日期等数字后缀尚未被移除
+    fn clean_source_proxies(proxies: &mut Vec<Proxy>, context: &mut ResolveContext) {
+        Self::strip_info_proxies(proxies, &mut context.info);
+        Self::normalize_proxies_name(proxies, &context.ad_phrases);
+    }
+
+    /// 清理节点名称：移除控制字符和零宽字符、广告词，合并连续空白，去除首尾的分隔符
+    /// 清理后为空时保留仅移除控制字符的名称
+    pub fn normalize_name(name: &str, ad_phrases: &[String]) -> String {
+        let cleaned: String = name
+            .chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .filter(|c| !matches!(c, '\u{200b}'..='\u{200f}' | '\u{feff}'))
+            .collect();
+        let mut without_ads = cleaned.clone();
+        for phrase in ad_phrases.iter().filter(|p| !p.is_empty()) {
+            without_ads = without_ads.replace(phrase.as_str(), " ");
+        }
+        let tidy = |name: &str| {
+            name.split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .trim_matches(|c: char| c.is_whitespace() || "|-_,，".contains(c))
+                .to_string()
+        };
+        let normalized = tidy(&without_ads);
+        if normalized.is_empty() {
+            tidy(&cleaned)
+        } else {
+            normalized
+        }
+    }
+
+    pub fn normalize_proxies_name(proxies: &mut [Proxy], ad_phrases: &[String]) {
+        for proxy in proxies {
+            let name = Self::normalize_name(proxy.get_name(), ad_phrases);
+            proxy.set_name(&name);
+        }
+    }
+
     /// 重置节点名称
     #[allow(dead_code)]
     pub fn unset_proxies_name(proxies: &mut Vec<Proxy>) {
@@ -296,31 +840,45 @@ impl SubManager {
         {
             for group in groups.iter_mut() {
                 if let Some(group_map) = group.as_mapping_mut() {
-                    if let Some(Value::String(filter)) =
-                        group_map.get(Value::String("filter".to_string()))
+                    // region 为自定义字段，按名称推断的地区筛选节点，mihomo 不识别，输出前移除
+                    let regions = group_map
+                        .remove(Value::String("region".to_string()))
+                        .map(|v| region_codes(&v));
+                    let regex = match group_map.get(Value::String("filter".to_string())) {
+                        Some(Value::String(filter)) => {
+                            Some(Regex::new(filter).expect("Invalid regex"))
+                        }
+                        _ => None,
+                    };
+                    if regex.is_none() && regions.is_none() {
+                        continue;
+                    }
+                    let is_match = |name: &str| {
+                        regex.as_ref().is_none_or(|re| re.is_match(name))
+                            && regions.as_ref().is_none_or(|codes| {
+                                region::infer_country_code(name).is_some_and(|c| codes.contains(&c))
+                            })
+                    };
+                    if let Some(proxies) = group_map
+                        .get_mut(Value::String("proxies".to_string()))
+                        .and_then(Value::as_sequence_mut)
                     {
-                        let regex = Regex::new(filter).expect("Invalid regex");
-                        if let Some(proxies) = group_map
-                            .get_mut(Value::String("proxies".to_string()))
-                            .and_then(Value::as_sequence_mut)
-                        {
-                            let mut removed_default = false;
-                            for proxy in new_proxies {
-                                if regex.is_match(proxy.get_name()) {
-                                    if !removed_default
-                                        && proxies
-                                            .first()
-                                            .is_some_and(|p| p.as_str().unwrap().eq("PROXY"))
-                                    {
-                                        proxies.remove(0);
-                                        removed_default = true;
-                                    }
-                                    proxies.push(Value::String(proxy.get_name().to_string()));
+                        let mut removed_default = false;
+                        for proxy in new_proxies {
+                            if is_match(proxy.get_name()) {
+                                if !removed_default
+                                    && proxies
+                                        .first()
+                                        .is_some_and(|p| p.as_str().unwrap().eq("PROXY"))
+                                {
+                                    proxies.remove(0);
+                                    removed_default = true;
                                 }
+                                proxies.push(Value::String(proxy.get_name().to_string()));
                             }
-                            if proxies.is_empty() {
-                                proxies.push(Value::String("DIRECT".to_string()));
-                            }
+                        }
+                        if proxies.is_empty() {
+                            proxies.push(Value::String("DIRECT".to_string()));
                         }
                     }
                 }
@@ -351,6 +909,19 @@ impl SubManager {
     }
 }
 
+// region 支持单个国家代码或列表，如 region: HK 或 region: [HK, MO]
+fn region_codes(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(code) => vec![code.to_uppercase()],
+        Value::Sequence(codes) => codes
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_uppercase)
+            .collect(),
+        _ => vec![],
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::path::PathBuf;
@@ -392,6 +963,193 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_parse_proxy_providers() {
+        let content = r#"
+proxy-providers:
+  remote:
+    type: http
+    url: https://example.com/sub.yaml
+    path: ./providers/remote.yaml
+  local:
+    type: file
+    path: ./local.yaml
+  inline:
+    type: inline
+"#;
+        let providers = SubManager::parse_proxy_providers(content);
+        assert_eq!(providers.len(), 2);
+        assert!(providers.contains(&ProxyProvider::Http {
+            name: "remote".to_string(),
+            url: "https://example.com/sub.yaml".to_string(),
+        }));
+        assert!(providers.contains(&ProxyProvider::File {
+            name: "local".to_string(),
+            path: "./local.yaml".to_string(),
+        }));
+        assert!(SubManager::parse_proxy_providers("ss://xxx").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_file_proxy_providers() {
+        let path = PathBuf::from_iter(vec![
+            env!("CARGO_MANIFEST_DIR"),
+            "..",
+            "tests",
+            "res",
+            "clashyaml_proxy_provider",
+        ]);
+        let proxies = SubManager::get_proxies_from_url(path.to_string_lossy().to_string()).await;
+        let expected =
+            SubManager::parse_from_path(path.with_file_name("clashyaml_proxies")).unwrap();
+        // 自身引用的 provider 被循环检测跳过，只保留 local 中的节点
+        assert_eq!(proxies.len(), expected.len());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_provider_depth_limit() {
+        let dir = std::env::temp_dir().join(format!("proxrs-provider-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..=MAX_PROVIDER_DEPTH + 1 {
+            let content = format!(
+                "proxies:\n  - {{ name: n{i}, type: ss, server: 1.1.1.{i}, port: 443, cipher: aes-128-gcm, password: p }}\nproxy-providers:\n  next:\n    type: file\n    path: ./p{}.yaml\n",
+                i + 1
+            );
+            fs::write(dir.join(format!("p{i}.yaml")), content).unwrap();
+        }
+        let proxies =
+            SubManager::get_proxies_from_url(dir.join("p0.yaml").to_string_lossy().to_string())
+                .await;
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(proxies.len(), MAX_PROVIDER_DEPTH + 1);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_unparsable_provider() {
+        let dir = std::env::temp_dir().join(format!("proxrs-broken-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let content = "proxies:\n  - { name: n0, type: ss, server: 1.1.1.1, port: 443, cipher: aes-128-gcm, password: p }\nproxy-providers:\n  broken:\n    type: file\n    path: ./broken.txt\n";
+        fs::write(dir.join("root.yaml"), content).unwrap();
+        fs::write(dir.join("broken.txt"), "<html>not a subscription</html>").unwrap();
+        let proxies =
+            SubManager::get_proxies_from_url(dir.join("root.yaml").to_string_lossy().to_string())
+                .await;
+        fs::remove_dir_all(&dir).unwrap();
+        // 无法解析的 provider 不产生节点，也不影响其它节点
+        assert_eq!(proxies.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_missing_provider_file() {
+        let dir = std::env::temp_dir().join(format!("proxrs-missing-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // path 指向的文件不存在，不能把 path 本身当作节点链接解析
+        let content = "proxies:\n  - { name: n0, type: ss, server: 1.1.1.1, port: 443, cipher: aes-128-gcm, password: p }\nproxy-providers:\n  missing:\n    type: file\n    path: ss://YWVzLTEyOC1nY206ZDljNTc3MzI4ZmIzNDlmZQ==@120.232.73.68:40676#HK\n";
+        fs::write(dir.join("root.yaml"), content).unwrap();
+        let proxies =
+            SubManager::get_proxies_from_url(dir.join("root.yaml").to_string_lossy().to_string())
+                .await;
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(proxies.len(), 1);
+        assert_eq!(proxies[0].get_name(), "n0");
+    }
+
+    #[test]
+    fn test_parse_url_safe_base64_content() {
+        let content = "c3M6Ly9ZV1Z6TFRFeU9DMW5ZMjA2WkRsak5UYzNNekk0Wm1Jek5EbG1aUT09QDEyMC4yMzIuNzMuNjg6NDA2NzYjSEs_PwpzczovL1lXVnpMVEV5T0MxblkyMDZaRGxqTlRjM016STRabUl6TkRsbVpRPT1AMTIwLjIzMi43My42ODo0NzAzNCNKUAo";
+        let proxies = SubManager::parse_content(content.to_string()).unwrap();
+        assert_eq!(proxies.len(), 2);
+        assert_eq!(proxies[0].get_name(), "HK??");
+        assert_eq!(proxies[1].get_name(), "JP");
+    }
+
+    #[test]
+    fn test_parse_gbk_base64_content() {
+        let link = "ss://YWVzLTEyOC1nY206ZDljNTc3MzI4ZmIzNDlmZQ==@120.232.73.68:40676#香港";
+        let (gbk, _, _) = encoding_rs::GBK.encode(link);
+        let content = base64::Engine::encode(&base64::prelude::BASE64_STANDARD, &gbk);
+        let proxies = SubManager::parse_base64_content(&content).unwrap();
+        assert_eq!(proxies[0].get_name(), "香港");
+
+        // 能被 GBK 解码但解不出节点的内容不当作 base64 订阅
+        assert!(SubManager::parse_base64_content("Q1GUZ7VDPZOASC9H").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_proxies_from_dir_and_glob() {
+        let dir = std::env::temp_dir().join(format!("proxrs-glob-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("a.yaml"),
+            "proxies:\n  - { name: a, type: ss, server: 1.1.1.1, port: 443, cipher: aes-128-gcm, password: p }\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("b.txt"),
+            "ss://YWVzLTEyOC1nY206ZDljNTc3MzI4ZmIzNDlmZQ==@120.232.73.68:40676#b\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join(".hidden"),
+            "ss://YWVzLTEyOC1nY206cA==@1.1.1.2:443#h\n",
+        )
+        .unwrap();
+
+        let proxies = SubManager::get_proxies_from_url(dir.to_string_lossy().to_string()).await;
+        assert_eq!(proxies.len(), 2);
+        assert_eq!(
+            proxies[0].sources,
+            vec![dir.join("a.yaml").to_string_lossy().to_string()]
+        );
+        assert_eq!(
+            proxies[1].sources,
+            vec![dir.join("b.txt").to_string_lossy().to_string()]
+        );
+
+        let pattern = dir.join("*.yaml").to_string_lossy().to_string();
+        let proxies = SubManager::get_proxies_from_url(pattern).await;
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(proxies.len(), 1);
+        assert_eq!(proxies[0].get_name(), "a");
+    }
+
+    #[tokio::test]
+    async fn test_exclude_dup_proxies_merge_sources() {
+        let link = "ss://YWVzLTEyOC1nY206ZDljNTc3MzI4ZmIzNDlmZQ==@120.232.73.68:40676#HK";
+        let mut first = Proxy::from_link(link.to_string()).unwrap();
+        first.add_source("a");
+        let mut second = Proxy::from_link(link.to_string()).unwrap();
+        second.add_source("b");
+        second.add_source("a");
+        let proxies =
+            SubManager::exclude_dup_proxies(vec![first, second], DedupPolicy::Strict).await;
+        assert_eq!(proxies.len(), 1);
+        assert_eq!(proxies[0].sources, vec!["a".to_string(), "b".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_exclude_dup_proxies_policy() {
+        let content = r#"
+proxies:
+  - { name: a, type: vless, server: 1.1.1.1, port: 443, uuid: 2cd6ed0f-636e-4e6c-9449-5a263d7a0fa5, network: ws, ws-opts: { path: /a } }
+  - { name: b, type: vless, server: 1.1.1.1, port: 443, uuid: 2cd6ed0f-636e-4e6c-9449-5a263d7a0fa5, network: ws, ws-opts: { path: /b } }
+  - { name: c, type: vless, server: 1.1.1.1, port: 443, uuid: 2cd6ed0f-636e-4e6c-9449-5a263d7a0fa5, network: ws, ws-opts: { path: /b }, servername: example.com }
+  - { name: d, type: vless, server: 1.1.1.1, port: 443, uuid: 2cd6ed0f-636e-4e6c-9449-5a263d7a0fa5, network: ws, ws-opts: { path: /a }, udp: true }
+"#;
+        let proxies = SubManager::parse_content(content.to_string()).unwrap();
+
+        // ws path 不同的节点不会被合并，只有 udp 不同的 d 与 a 重复，保留字段更全的 d
+        let strict = SubManager::exclude_dup_proxies(proxies.clone(), DedupPolicy::Strict).await;
+        let mut names = strict.iter().map(|p| p.get_name()).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["b", "c", "d"]);
+
+        // 仅比较端点时全部合并，保留字段最全的 c
+        let loose = SubManager::exclude_dup_proxies(proxies, DedupPolicy::Loose).await;
+        assert_eq!(loose.len(), 1);
+        assert_eq!(loose[0].get_name(), "c");
+    }
+
     #[test]
     fn test_regex_filter() {
         let filter = "台湾|TW|Tw|Taiwan|新北|彰化|CHT|HINET";
@@ -400,6 +1158,147 @@ mod test {
         assert!(!is_match);
     }
 
+    #[test]
+    fn test_region_groups() {
+        let template = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../tests/res/clash_region_template.yaml");
+        let proxies = SubManager::parse_content(
+            "ss://cmM0LW1kNToydnpobzU=@120.241.144.101:2410#%F0%9F%87%AD%F0%9F%87%B0%20Premium\n\
+            ss://cmM0LW1kNToydnpobzU=@120.241.144.102:2410#%E6%9D%B1%E4%BA%AC%20Premium\n\
+            ss://cmM0LW1kNToydnpobzU=@120.241.144.103:2410#SG%2001"
+                .to_string(),
+        )
+        .unwrap();
+        let content =
+            SubManager::get_clash_config_content(template.to_string_lossy().to_string(), &proxies)
+                .unwrap();
+        let yaml: Value = serde_yaml::from_str(&content).unwrap();
+        let groups = yaml["proxy-groups"].as_sequence().unwrap();
+        let members = |i: usize| -> Vec<&str> {
+            groups[i]["proxies"]
+                .as_sequence()
+                .unwrap()
+                .iter()
+                .map(|p| p.as_str().unwrap())
+                .collect()
+        };
+        assert_eq!(members(0), vec!["🇭🇰 Premium"]);
+        assert_eq!(members(1), vec!["東京 Premium"]);
+        assert_eq!(members(2), vec!["PROXY"]);
+        assert!(groups[0].get("region").is_none());
+    }
+
+    #[test]
+    fn test_classify_info_node() {
+        assert_eq!(
+            SubManager::classify_info_node("剩余流量：163.97 GB"),
+            Some(InfoNode::Traffic("163.97 GB".to_string()))
+        );
+        assert_eq!(
+            SubManager::classify_info_node("套餐到期：2025-10-01"),
+            Some(InfoNode::Expire("2025-10-01".to_string()))
+        );
+        assert_eq!(
+            SubManager::classify_info_node("距离下次重置剩余：12 天"),
+            Some(InfoNode::Reset("12 天".to_string()))
+        );
+        assert_eq!(
+            SubManager::classify_info_node("官网 example.com"),
+            Some(InfoNode::Website("example.com".to_string()))
+        );
+        assert_eq!(
+            SubManager::classify_info_node("加入 TG群 获取更多"),
+            Some(InfoNode::Notice)
+        );
+        assert_eq!(
+            SubManager::classify_info_node("📢 频道 @example"),
+            Some(InfoNode::Notice)
+        );
+        assert_eq!(
+            SubManager::classify_info_node("🇯🇵 日本 01 Telegram 频道专用"),
+            None
+        );
+        assert_eq!(SubManager::classify_info_node("HK 02 建议晚高峰使用"), None);
+        assert_eq!(SubManager::classify_info_node("🇭🇰 香港 01"), None);
+        assert_eq!(SubManager::classify_info_node("US 1.5x 流量"), None);
+    }
+
+    #[test]
+    fn test_strip_info_proxies() {
+        let mut proxies = SubManager::parse_content(
+            "ss://cmM0LW1kNToydnpobzU=@127.0.0.1:1#%E5%89%A9%E4%BD%99%E6%B5%81%E9%87%8F%EF%BC%9A163.97%20GB\n\
+            ss://cmM0LW1kNToydnpobzU=@127.0.0.1:2#%E5%A5%97%E9%A4%90%E5%88%B0%E6%9C%9F%EF%BC%9A2025-10-01\n\
+            ss://cmM0LW1kNToydnpobzU=@120.241.144.101:2410#HK"
+                .to_string(),
+        )
+        .unwrap();
+        let mut info = SourceInfo::default();
+        SubManager::strip_info_proxies(&mut proxies, &mut info);
+        assert_eq!(proxies.len(), 1);
+        assert_eq!(
+            info,
+            SourceInfo {
+                remaining_traffic: Some("163.97 GB".to_string()),
+                expire: Some("2025-10-01".to_string()),
+                reset: None,
+                website: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_proxies_from_urls_info_nodes() {
+        let path = std::env::temp_dir().join(format!("proxrs-info-{}.txt", std::process::id()));
+        fs::write(
+            &path,
+            "ss://cmM0LW1kNToydnpobzU=@127.0.0.1:1#%E5%88%B0%E6%9C%9F%3A2025-12-31\n\
+            ss://cmM0LW1kNToydnpobzU=@120.241.144.101:2410#HK%2001%20%40ads\n\
+            ss://cmM0LW1kNToydnpobzU=@120.241.144.102:2410#HK%2002",
+        )
+        .unwrap();
+        let source = path.to_string_lossy().to_string();
+        let parsed = SubManager::get_proxies_from_urls(
+            &vec![source.clone()],
+            DedupPolicy::Strict,
+            &["@ads".to_string()],
+        )
+        .await;
+        fs::remove_file(&path).unwrap();
+        // 信息节点在去除数字后缀之前移除，到期日期完整保留
+        assert_eq!(parsed.infos[&source].expire.as_deref(), Some("2025-12-31"));
+        let names: Vec<&str> = parsed.proxies.iter().map(|p| p.get_name()).collect();
+        assert_eq!(names, vec!["HK 1", "HK 2"]);
+    }
+
+    #[test]
+    fn test_normalize_name() {
+        let ads = vec!["@freenodes".to_string(), "关注频道".to_string()];
+        assert_eq!(
+            SubManager::normalize_name("  香港\u{200b}  01 | @freenodes\r", &ads),
+            "香港 01"
+        );
+        assert_eq!(
+            SubManager::normalize_name("关注频道\t美国  02", &ads),
+            "美国 02"
+        );
+        assert_eq!(SubManager::normalize_name("@freenodes", &ads), "@freenodes");
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let proxies = SubManager::parse_content(
+            "ss://cmM0LW1kNToydnpobzU=@120.241.144.101:2410#a\n\
+            ss://cmM0LW1kNToydnpobzU=@120.241.144.101:2410#b\n\
+            ss://cmM0LW1kNToydnpobzU=@120.241.144.101:2411#a"
+                .to_string(),
+        )
+        .unwrap();
+        let fingerprint = SubManager::fingerprint(&proxies[0]);
+        assert_eq!(fingerprint.len(), 16);
+        assert_eq!(fingerprint, SubManager::fingerprint(&proxies[1]));
+        assert_ne!(fingerprint, SubManager::fingerprint(&proxies[2]));
+    }
+
     #[test]
     fn test_rename_dup_proxies_name() {
         let content = String::from(
@@ -438,7 +1337,9 @@ mod test {
             "vmess://YXV0bzo5MjA0YWZjZC0wMjNlLTc4MWYtMWFiYy1jMTJlZmNjZDEzNDRAMTIyLjE5NS4xODkuMTI0OjMzODAw?remarks=Tokyo-Akamai-H&path=/ray&obfs=websocket&tls=1&alterId=0".to_string(),
             "vmess://YXV0bzo5MjA0YWZjZC0wMjNlLTc4MWYtMWFiYy1jMTJlZmNjZDEzNDRANDMuMjQ4LjExOS4xNDU6MzM0MDc?remarks=%E9%A6%99%E6%B8%AF%E9%98%BF%E9%87%8C%E4%BA%91-H&path=/ray&obfs=websocket&tls=1&alterId=0".to_string(),
         ];
-        let proxies = SubManager::get_proxies_from_urls(&urls).await;
+        let proxies = SubManager::get_proxies_from_urls(&urls, DedupPolicy::Strict, &[])
+            .await
+            .proxies;
         let release_clash_template_path =
             "/Users/reajason/RustroverProjects/clash-butler/conf/clash_release.yaml".to_string();
         let save_path =
@@ -450,7 +1351,9 @@ mod test {
     #[tokio::test]
     async fn test_rename() {
         let urls = vec!["/Users/reajason/RustroverProjects/clash-butler/clash.yaml".to_string()];
-        let mut proxies = SubManager::get_proxies_from_urls(&urls).await;
+        let mut proxies = SubManager::get_proxies_from_urls(&urls, DedupPolicy::Strict, &[])
+            .await
+            .proxies;
         SubManager::rename_dup_proxies_name(&mut proxies);
         let release_clash_template_path =
             "/Users/reajason/RustroverProjects/clash-butler/conf/clash_release.yaml".to_string();
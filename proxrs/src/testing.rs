@@ -0,0 +1,12 @@
+//! 测试用的节点构造函数，供 proxrs 和 clash-butler 的测试共用
+
+use crate::protocol::Proxy;
+
+/// 服务器和密码固定的 ss 节点，端口不同即为不同的节点
+pub fn proxy(port: u16, name: &str) -> Proxy {
+    Proxy::from_link(format!(
+        "ss://YWVzLTEyOC1nY206ZDljNTc3MzI4ZmIzNDlmZQ==@120.232.73.68:{}#{}",
+        port, name
+    ))
+    .unwrap()
+}
@@ -0,0 +1,333 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use regex::Regex;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::info;
+
+use crate::protocol::Proxy;
+use crate::protocol::ProxyType;
+
+/// 解析后对节点依次执行的过滤和变换步骤，对应 config.toml 中的 [[transforms]]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransformStep {
+    /// 仅保留名称匹配正则的节点
+    Include { pattern: String },
+    /// 移除名称匹配正则的节点
+    Exclude { pattern: String },
+    /// 按协议过滤，include 为空时不限制
+    Protocol {
+        #[serde(default)]
+        include: Vec<ProxyType>,
+        #[serde(default)]
+        exclude: Vec<ProxyType>,
+    },
+    /// 按服务器过滤，支持 IP、CIDR，其它写法视为匹配域名的正则
+    Server {
+        #[serde(default)]
+        include: Vec<String>,
+        #[serde(default)]
+        exclude: Vec<String>,
+    },
+    /// 按端口过滤，支持 443 或 8000-9000 的写法
+    Port {
+        #[serde(default)]
+        include: Vec<String>,
+        #[serde(default)]
+        exclude: Vec<String>,
+    },
+    /// 正则替换名称，replace 中可使用 $1、${name} 引用捕获组
+    Rename { pattern: String, replace: String },
+    /// 为名称加前缀，source 为匹配节点来源的正则，不填则作用于全部节点
+    Prefix {
+        value: String,
+        source: Option<String>,
+    },
+    /// 为名称加后缀，source 同 prefix
+    Suffix {
+        value: String,
+        source: Option<String>,
+    },
+    /// 排序，影响后续 limit 保留的节点
+    Sort {
+        by: SortKey,
+        #[serde(default)]
+        desc: bool,
+    },
+    /// 仅保留前 count 个节点
+    Limit { count: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    Name,
+    Type,
+    Server,
+    Port,
+}
+
+/// 按顺序执行所有变换步骤，正则或规则写错时返回错误
+pub fn apply_transforms(
+    mut proxies: Vec<Proxy>,
+    steps: &[TransformStep],
+) -> Result<Vec<Proxy>, Box<dyn std::error::Error>> {
+    for step in steps {
+        let before = proxies.len();
+        proxies = apply_step(proxies, step)?;
+        info!("transform {:?}: {} -> {}", step, before, proxies.len());
+    }
+    Ok(proxies)
+}
+
+fn apply_step(
+    mut proxies: Vec<Proxy>,
+    step: &TransformStep,
+) -> Result<Vec<Proxy>, Box<dyn std::error::Error>> {
+    match step {
+        TransformStep::Include { pattern } => {
+            let re = Regex::new(pattern)?;
+            proxies.retain(|p| re.is_match(p.get_name()));
+        }
+        TransformStep::Exclude { pattern } => {
+            let re = Regex::new(pattern)?;
+            proxies.retain(|p| !re.is_match(p.get_name()));
+        }
+        TransformStep::Protocol { include, exclude } => {
+            proxies.retain(|p| {
+                (include.is_empty() || include.contains(&p.proxy_type))
+                    && !exclude.contains(&p.proxy_type)
+            });
+        }
+        TransformStep::Server { include, exclude } => {
+            let include = parse_rules(include, ServerRule::parse)?;
+            let exclude = parse_rules(exclude, ServerRule::parse)?;
+            proxies.retain(|p| {
+                let server = p.get_server();
+                (include.is_empty() || include.iter().any(|r| r.matches(server)))
+                    && !exclude.iter().any(|r| r.matches(server))
+            });
+        }
+        TransformStep::Port { include, exclude } => {
+            let include = parse_rules(include, parse_port_range)?;
+            let exclude = parse_rules(exclude, parse_port_range)?;
+            proxies.retain(|p| {
+                let port = p.get_port();
+                let in_range = |(start, end): &(u16, u16)| (*start..=*end).contains(&port);
+                (include.is_empty() || include.iter().any(in_range))
+                    && !exclude.iter().any(in_range)
+            });
+        }
+        TransformStep::Rename { pattern, replace } => {
+            let re = Regex::new(pattern)?;
+            for proxy in &mut proxies {
+                let name = re
+                    .replace_all(proxy.get_name(), replace.as_str())
+                    .to_string();
+                proxy.set_name(&name);
+            }
+        }
+        TransformStep::Prefix { value, source } => {
+            let source = source.as_deref().map(Regex::new).transpose()?;
+            for proxy in &mut proxies {
+                if source_matches(proxy, source.as_ref()) {
+                    let name = format!("{}{}", value, proxy.get_name());
+                    proxy.set_name(&name);
+                }
+            }
+        }
+        TransformStep::Suffix { value, source } => {
+            let source = source.as_deref().map(Regex::new).transpose()?;
+            for proxy in &mut proxies {
+                if source_matches(proxy, source.as_ref()) {
+                    let name = format!("{}{}", proxy.get_name(), value);
+                    proxy.set_name(&name);
+                }
+            }
+        }
+        TransformStep::Sort { by, desc } => {
+            match by {
+                SortKey::Name => proxies.sort_by(|a, b| a.get_name().cmp(b.get_name())),
+                SortKey::Type => proxies.sort_by(|a, b| a.proxy_type.cmp(&b.proxy_type)),
+                SortKey::Server => proxies.sort_by(|a, b| a.get_server().cmp(b.get_server())),
+                SortKey::Port => proxies.sort_by_key(|p| p.get_port()),
+            }
+            if *desc {
+                proxies.reverse();
+            }
+        }
+        TransformStep::Limit { count } => proxies.truncate(*count),
+    }
+    Ok(proxies)
+}
+
+fn source_matches(proxy: &Proxy, source: Option<&Regex>) -> bool {
+    match source {
+        Some(re) => proxy.sources.iter().any(|s| re.is_match(s)),
+        None => true,
+    }
+}
+
+fn parse_rules<T>(
+    rules: &[String],
+    parse: fn(&str) -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+    rules.iter().map(|r| parse(r.trim())).collect()
+}
+
+fn parse_port_range(rule: &str) -> Result<(u16, u16), Box<dyn std::error::Error>> {
+    match rule.split_once('-') {
+        Some((start, end)) => Ok((start.trim().parse()?, end.trim().parse()?)),
+        None => {
+            let port = rule.parse()?;
+            Ok((port, port))
+        }
+    }
+}
+
+enum ServerRule {
+    Cidr(IpAddr, u8),
+    Domain(Regex),
+}
+
+impl ServerRule {
+    fn parse(rule: &str) -> Result<ServerRule, Box<dyn std::error::Error>> {
+        if let Some((ip, prefix)) = rule.split_once('/') {
+            if let Ok(ip) = IpAddr::from_str(ip) {
+                let prefix: u8 = prefix.parse()?;
+                let max = if ip.is_ipv4() { 32 } else { 128 };
+                if prefix > max {
+                    return Err(format!("invalid cidr prefix: {}", rule).into());
+                }
+                return Ok(ServerRule::Cidr(ip, prefix));
+            }
+        }
+        if let Ok(ip) = IpAddr::from_str(rule) {
+            let prefix = if ip.is_ipv4() { 32 } else { 128 };
+            return Ok(ServerRule::Cidr(ip, prefix));
+        }
+        Ok(ServerRule::Domain(Regex::new(rule)?))
+    }
+
+    fn matches(&self, server: &str) -> bool {
+        match self {
+            ServerRule::Cidr(network, prefix) => match IpAddr::from_str(server) {
+                Ok(ip) => cidr_contains(network, *prefix, &ip),
+                Err(_) => false,
+            },
+            ServerRule::Domain(re) => re.is_match(server),
+        }
+    }
+}
+
+fn cidr_contains(network: &IpAddr, prefix: u8, ip: &IpAddr) -> bool {
+    match (network, ip) {
+        (IpAddr::V4(net), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(*net) & mask == u32::from(*ip) & mask
+        }
+        (IpAddr::V6(net), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(*net) & mask == u128::from(*ip) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn proxies() -> Vec<Proxy> {
+        let links = [
+            "ss://YWVzLTEyOC1nY206ZDljNTc3MzI4ZmIzNDlmZQ==@10.0.0.8:443#JP 02",
+            "trojan://pwd@hk.example.com:8443?sni=hk.example.com#HK 03",
+            "hysteria2://pwd@188.68.234.53:36604/?sni=www.bing.com#US 04",
+        ];
+        let mut proxies = vec![testing::proxy(40676, "HK 01")];
+        proxies.extend(
+            links
+                .iter()
+                .map(|link| Proxy::from_link(link.to_string()).unwrap()),
+        );
+        for (i, proxy) in proxies.iter_mut().enumerate() {
+            proxy.add_source(if i % 2 == 0 { "sub-a" } else { "sub-b" });
+        }
+        proxies
+    }
+
+    fn names(proxies: &[Proxy]) -> Vec<&str> {
+        proxies.iter().map(|p| p.get_name()).collect()
+    }
+
+    #[test]
+    fn test_filter_steps() {
+        let steps: Vec<TransformStep> = serde_json::from_str(
+            r#"[
+                {"type": "exclude", "pattern": "^US"},
+                {"type": "protocol", "exclude": ["trojan"]},
+                {"type": "server", "exclude": ["10.0.0.0/8"]}
+            ]"#,
+        )
+        .unwrap();
+        let result = apply_transforms(proxies(), &steps).unwrap();
+        assert_eq!(names(&result), vec!["HK 01"]);
+
+        let steps = vec![
+            TransformStep::Include {
+                pattern: "HK".to_string(),
+            },
+            TransformStep::Server {
+                include: vec![r"\.example\.com$".to_string()],
+                exclude: vec![],
+            },
+        ];
+        let result = apply_transforms(proxies(), &steps).unwrap();
+        assert_eq!(names(&result), vec!["HK 03"]);
+
+        let steps = vec![TransformStep::Port {
+            include: vec!["443".to_string(), "8000-9000".to_string()],
+            exclude: vec![],
+        }];
+        let result = apply_transforms(proxies(), &steps).unwrap();
+        assert_eq!(names(&result), vec!["JP 02", "HK 03"]);
+    }
+
+    #[test]
+    fn test_rename_and_sort_steps() {
+        let steps = vec![
+            TransformStep::Rename {
+                pattern: r"^(\w+) (\d+)$".to_string(),
+                replace: "$1-$2".to_string(),
+            },
+            TransformStep::Prefix {
+                value: "[B]".to_string(),
+                source: Some("sub-b".to_string()),
+            },
+            TransformStep::Suffix {
+                value: "!".to_string(),
+                source: None,
+            },
+            TransformStep::Sort {
+                by: SortKey::Port,
+                desc: true,
+            },
+            TransformStep::Limit { count: 2 },
+        ];
+        let result = apply_transforms(proxies(), &steps).unwrap();
+        assert_eq!(names(&result), vec!["HK-01!", "[B]US-04!"]);
+    }
+
+    #[test]
+    fn test_invalid_rule() {
+        let steps = vec![TransformStep::Port {
+            include: vec!["abc".to_string()],
+            exclude: vec![],
+        }];
+        assert!(apply_transforms(proxies(), &steps).is_err());
+        assert!(ServerRule::parse("1.1.1.1/33").is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::io;
+
+use proxrs::protocol::Proxy;
+use proxrs::sub::SubManager;
+
+use crate::clash::ClashMeta;
+use crate::clash::DelayTestConfig;
+
+// 测试配置中包含全部节点的分组
+pub const TEST_GROUP_NAME: &str = "PROXY";
+
+/// 节点测试后端，负责加载节点、测试延迟和提供代理地址
+pub trait TestBackend {
+    // 流程中由 load_proxies 按需启动
+    #[allow(dead_code)]
+    async fn start(&mut self) -> Result<(), Box<dyn Error>>;
+
+    fn stop(&mut self) -> io::Result<()>;
+
+    /// 加载一组待测试的节点，后端未运行时启动，运行中时切换节点列表
+    async fn load_proxies(&mut self, proxies: &[Proxy]) -> Result<(), Box<dyn Error>>;
+
+    /// 对已加载的全部节点测试一次延迟，返回有延迟的节点
+    async fn delay_test(
+        &mut self,
+        config: &DelayTestConfig,
+    ) -> Result<HashMap<String, i64>, Box<dyn Error>>;
+
+    /// 切换 proxy_url 使用的节点
+    // select、proxy_url 和 node_proxy_url 供 AI 服务检测使用，流程中目前直接使用 ClashMeta
+    #[allow(dead_code)]
+    async fn select(&mut self, node: &str) -> Result<(), Box<dyn Error>>;
+
+    /// 当前选中节点的代理地址
+    #[allow(dead_code)]
+    fn proxy_url(&self) -> String;
+
+    /// 直接使用某个节点的代理地址，不支持时返回 None
+    #[allow(dead_code)]
+    fn node_proxy_url(&self, node: &str) -> Option<String>;
+
+    /// 检查后端是否仍在运行
+    fn check_alive(&mut self) -> Result<(), Box<dyn Error>>;
+
+    /// 运行日志，用于归类节点失败原因
+    fn log(&self) -> String;
+}
+
+/// 基于 mihomo 内核的测试后端
+pub struct MihomoBackend {
+    clash_meta: ClashMeta,
+    template_path: String,
+}
+
+impl MihomoBackend {
+    pub fn new(clash_meta: ClashMeta, template_path: &str) -> Self {
+        MihomoBackend {
+            clash_meta,
+            template_path: template_path.to_string(),
+        }
+    }
+}
+
+impl TestBackend for MihomoBackend {
+    async fn start(&mut self) -> Result<(), Box<dyn Error>> {
+        self.clash_meta.start().await
+    }
+
+    fn stop(&mut self) -> io::Result<()> {
+        self.clash_meta.shutdown()
+    }
+
+    async fn load_proxies(&mut self, proxies: &[Proxy]) -> Result<(), Box<dyn Error>> {
+        SubManager::save_proxies_into_clash_file(
+            &proxies.to_vec(),
+            self.template_path.clone(),
+            format!("{}/config.yaml", self.clash_meta.test_path()),
+        );
+        let expected = proxies
+            .iter()
+            .map(|proxy| proxy.get_name().to_string())
+            .collect::<HashSet<String>>();
+        self.clash_meta
+            .reload_or_start(TEST_GROUP_NAME, &expected)
+            .await
+    }
+
+    async fn delay_test(
+        &mut self,
+        config: &DelayTestConfig,
+    ) -> Result<HashMap<String, i64>, Box<dyn Error>> {
+        self.clash_meta.test_group(TEST_GROUP_NAME, config).await
+    }
+
+    async fn select(&mut self, node: &str) -> Result<(), Box<dyn Error>> {
+        self.clash_meta.set_group_proxy(TEST_GROUP_NAME, node).await
+    }
+
+    fn proxy_url(&self) -> String {
+        self.clash_meta.proxy_url.clone()
+    }
+
+    fn node_proxy_url(&self, node: &str) -> Option<String> {
+        self.clash_meta.node_proxy_url(node)
+    }
+
+    fn check_alive(&mut self) -> Result<(), Box<dyn Error>> {
+        self.clash_meta.check_alive()
+    }
+
+    fn log(&self) -> String {
+        fs::read_to_string(self.clash_meta.log_path()).unwrap_or_default()
+    }
+}
@@ -1,95 +1,413 @@
 #![allow(dead_code)]
 
+use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
 use std::fs::File;
+use std::io;
+use std::net::Ipv4Addr;
+use std::net::TcpListener;
+use std::path::Path;
 use std::process::Child;
 use std::process::Command;
 use std::process::Stdio;
 use std::time::Duration;
+use std::time::Instant;
 
-use reqwest::Client;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
 use serde::Deserialize;
 use serde::Serialize;
-use serde_json::json;
-use serde_json::Value;
+use serde_yaml::Mapping;
+use serde_yaml::Sequence;
 use tokio::time::sleep;
 use tracing::info;
+use tracing::warn;
+
+use crate::controller::Controller;
+use crate::controller::ControllerError;
+use crate::controller::ProxyInfo;
+use crate::controller::Version;
+
+// 等待内核就绪的最长时间，首次启动需要下载 geo 文件
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+// 重启后等待内核就绪的最长时间
+const RESTART_TIMEOUT: Duration = Duration::from_secs(10);
+// 热加载配置后等待新节点列表出现的最长时间
+const RELOAD_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(1);
+// 内核异常时附带的日志行数
+const LOG_TAIL_LINES: usize = 20;
+// 记录内核进程号的文件，位于 test_path 下，用于清理上次运行残留的内核
+const PID_FILE_NAME: &str = "core.pid";
+// 结束残留内核后等待端口释放的最长时间
+const PORT_RELEASE_TIMEOUT: Duration = Duration::from_secs(3);
+const SECRET_LEN: usize = 32;
 
 pub struct ClashMeta {
     pub external_port: u64,
     pub mixed_port: u64,
     pub proxy_url: String,
     pub external_url: String,
+    controller: Controller,
+    secret: String,
     core_path: String,
     test_path: String,
     log_path: String,
+    // 节点名称到独立监听端口的映射
+    node_listeners: BTreeMap<String, u16>,
+    // 分配到的端口在交给内核之前保持占用，避免期间被其他进程占用
+    reserved_ports: Vec<TcpListener>,
     process: Option<Child>,
 }
 
 impl ClashMeta {
     pub fn new(external_port: u64, mixed_port: u64) -> Self {
+        let external_url = format!("http://127.0.0.1:{}", external_port);
         ClashMeta {
             external_port,
             mixed_port,
-            external_url: format!("http://127.0.0.1:{}", external_port),
+            controller: Controller::new(&external_url, "").expect("控制接口地址有效"),
+            external_url,
             proxy_url: format!("http://127.0.0.1:{}", mixed_port),
             process: None,
+            secret: String::new(),
             core_path: "clash-meta/mihomo".to_string(),
             test_path: "subs/test".to_string(),
             log_path: "logs/clash.log".to_string(),
+            node_listeners: BTreeMap::new(),
+            reserved_ports: Vec::new(),
         }
     }
 
+    /// 使用系统分配的空闲端口和随机 secret，避免与其他运行或本机的 Clash 冲突
+    pub fn with_free_ports() -> io::Result<Self> {
+        // 两个端口一直占用到内核启动前，保证分配到的端口不重复且不被其他进程占用
+        let external = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let mixed = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let mut clash_meta = ClashMeta::new(
+            external.local_addr()?.port() as u64,
+            mixed.local_addr()?.port() as u64,
+        );
+        clash_meta.reserved_ports = vec![external, mixed];
+        clash_meta.secret = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(SECRET_LEN)
+            .map(char::from)
+            .collect();
+        clash_meta.controller = Controller::new(&clash_meta.external_url, &clash_meta.secret)
+            .map_err(io::Error::other)?;
+        Ok(clash_meta)
+    }
+
+    /// 指定内核的工作目录和日志文件，并行测试时每个内核使用独立的目录
+    pub fn with_work_dir(mut self, test_path: &str, log_path: &str) -> Self {
+        self.test_path = test_path.to_string();
+        self.log_path = log_path.to_string();
+        self
+    }
+
+    /// 为每个节点分配独立的本地端口，启动后可通过 node_proxy_url 直接使用该节点而无需切换分组
+    pub fn set_node_listeners(&mut self, nodes: &[String]) -> io::Result<()> {
+        // 所有端口一直占用到内核启动或热加载前，保证分配到的端口不重复且不被其他进程占用
+        let sockets = nodes
+            .iter()
+            .map(|_| TcpListener::bind((Ipv4Addr::LOCALHOST, 0)))
+            .collect::<io::Result<Vec<_>>>()?;
+        self.node_listeners = nodes
+            .iter()
+            .zip(&sockets)
+            .map(|(node, socket)| Ok((node.clone(), socket.local_addr()?.port())))
+            .collect::<io::Result<_>>()?;
+        self.reserved_ports.extend(sockets);
+        Ok(())
+    }
+
+    /// 释放占用的端口，在内核绑定这些端口之前调用
+    fn release_reserved_ports(&mut self) {
+        self.reserved_ports.clear();
+    }
+
+    pub fn node_proxy_url(&self, node: &str) -> Option<String> {
+        self.node_listeners
+            .get(node)
+            .map(|port| format!("http://127.0.0.1:{}", port))
+    }
+
+    pub fn test_path(&self) -> &str {
+        &self.test_path
+    }
+
+    pub fn log_path(&self) -> &str {
+        &self.log_path
+    }
+
     pub async fn start(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.kill_stale_core();
+        self.write_runtime_options()?;
         let log_file = File::create(&self.log_path)?;
+        self.release_reserved_ports();
+        self.ensure_ports_free().await?;
 
         let clash_process = Command::new(&self.core_path)
             .arg("-d")
             .arg(&self.test_path)
-            .stdout(Stdio::from(log_file.try_clone().unwrap()))
-            .stdout(Stdio::from(log_file))
+            .stdout(Stdio::from(log_file.try_clone()?))
+            .stderr(Stdio::from(log_file))
             .spawn()?;
+        if let Err(e) = fs::write(self.pid_path(), clash_process.id().to_string()) {
+            warn!("内核进程号写入失败, {}", e);
+        }
+        self.process = Some(clash_process);
 
-        sleep(Duration::from_secs(5)).await;
+        let version = self.wait_ready(STARTUP_TIMEOUT).await?;
+        info!("原神启动！ 版本号：{}", version.version);
+        Ok(())
+    }
 
-        let response = reqwest::get(format!("{}/version", &self.external_url)).await?;
-        let res = response.json::<ClashVersion>().await?;
-        info!("原神启动！ 版本号：{}", res.version);
-        self.process = Some(clash_process);
+    /// 将端口和 secret 写入测试配置，覆盖模板中的 mixed-port、external-controller 和 secret
+    fn write_runtime_options(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let config_path = Path::new(&self.test_path).join("config.yaml");
+        let content = fs::read_to_string(&config_path)
+            .map_err(|e| format!("读取测试配置 {} 失败, {}", config_path.display(), e))?;
+        let mut config: Mapping = serde_yaml::from_str(&content)?;
+        config.insert("mixed-port".into(), self.mixed_port.into());
+        config.insert(
+            "external-controller".into(),
+            format!("127.0.0.1:{}", self.external_port).into(),
+        );
+        config.insert("secret".into(), self.secret.clone().into());
+        if !self.node_listeners.is_empty() {
+            let listeners = self
+                .node_listeners
+                .iter()
+                .map(|(node, port)| {
+                    let mut listener = Mapping::new();
+                    listener.insert("name".into(), format!("node-{}", port).into());
+                    listener.insert("type".into(), "mixed".into());
+                    listener.insert("listen".into(), "127.0.0.1".into());
+                    listener.insert("port".into(), (*port).into());
+                    listener.insert("proxy".into(), node.clone().into());
+                    listener.into()
+                })
+                .collect::<Sequence>();
+            config.insert("listeners".into(), listeners.into());
+        }
+        fs::write(&config_path, serde_yaml::to_string(&config)?)?;
         Ok(())
     }
 
-    pub async fn restart(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let client = Client::builder().timeout(Duration::from_secs(5)).build()?;
-        let response = client
-            .post(format!("{}/restart", &self.external_url))
-            .json(&json!({"path": self.test_path,"payload": ""}))
-            .send()
-            .await?;
+    /// 轮询控制接口直到内核就绪，内核提前退出或超时时返回附带日志末尾的错误
+    async fn wait_ready(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Version, Box<dyn std::error::Error>> {
+        let controller = self.controller.with_timeout(Duration::from_millis(500));
+        let deadline = Instant::now() + timeout;
+        let mut interval = POLL_INTERVAL;
+        loop {
+            self.check_alive()?;
+            if let Ok(version) = controller.version().await {
+                return Ok(version);
+            }
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "内核在 {} 秒内未就绪，{} 末尾日志：\n{}",
+                    timeout.as_secs(),
+                    self.log_path,
+                    self.log_tail()
+                )
+                .into());
+            }
+            sleep(interval).await;
+            interval = (interval * 2).min(MAX_POLL_INTERVAL);
+        }
+    }
 
-        if response.status().is_success() {
-            info!("内核重启成功");
-            sleep(Duration::from_secs(2)).await;
-        } else {
-            info!("内核重启失败: {}", response.status());
+    /// 检查内核进程是否仍在运行，已退出时返回附带日志末尾的错误
+    pub fn check_alive(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(process) = self.process.as_mut() {
+            if let Some(status) = process.try_wait()? {
+                self.process = None;
+                return Err(format!(
+                    "内核已退出（{}），{} 末尾日志：\n{}",
+                    status,
+                    self.log_path,
+                    self.log_tail()
+                )
+                .into());
+            }
         }
         Ok(())
     }
 
-    pub fn stop(mut self) -> std::io::Result<()> {
+    fn log_tail(&self) -> String {
+        let content = fs::read_to_string(&self.log_path).unwrap_or_default();
+        tail_lines(&content, LOG_TAIL_LINES)
+    }
+
+    pub async fn restart(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        match self.controller.restart(&self.test_path).await {
+            Ok(()) => {
+                self.wait_ready(RESTART_TIMEOUT).await?;
+                info!("内核重启成功");
+            }
+            Err(ControllerError::Status { status, .. }) => {
+                info!("内核重启失败: {}", status);
+            }
+            Err(e) => return Err(e.into()),
+        }
+        Ok(())
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.process.is_some()
+    }
+
+    /// 通过 PUT /configs 热加载测试配置，并等待分组中出现新的节点列表
+    pub async fn reload(
+        &mut self,
+        group_name: &str,
+        expected: &HashSet<String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.check_alive()?;
+        self.write_runtime_options()?;
+        let config_path = fs::canonicalize(Path::new(&self.test_path).join("config.yaml"))?;
+        self.release_reserved_ports();
+        self.controller
+            .with_timeout(Duration::from_secs(10))
+            .put_configs(&config_path.to_string_lossy(), true)
+            .await
+            .map_err(|e| format!("热加载配置失败: {}", e))?;
+
+        let deadline = Instant::now() + RELOAD_TIMEOUT;
+        let mut interval = POLL_INTERVAL;
+        loop {
+            self.check_alive()?;
+            if let Ok(group) = self.get_group(group_name).await {
+                if expected.iter().all(|name| group.all.contains(name)) {
+                    return Ok(());
+                }
+            }
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "热加载后 {} 秒内分组 {} 未出现新的节点列表",
+                    RELOAD_TIMEOUT.as_secs(),
+                    group_name
+                )
+                .into());
+            }
+            sleep(interval).await;
+            interval = (interval * 2).min(MAX_POLL_INTERVAL);
+        }
+    }
+
+    /// 内核运行中时热加载配置，热加载失败或内核未运行时重新启动内核
+    pub async fn reload_or_start(
+        &mut self,
+        group_name: &str,
+        expected: &HashSet<String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.is_running() {
+            match self.reload(group_name, expected).await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    warn!("{}，重新启动内核", e);
+                    self.shutdown()?;
+                }
+            }
+        }
+        self.start().await
+    }
+
+    pub fn stop(mut self) -> io::Result<()> {
+        self.shutdown()
+    }
+
+    /// 结束并回收内核进程，drop 时也会调用，保证异常退出时不残留内核占用端口
+    pub fn shutdown(&mut self) -> io::Result<()> {
         if let Some(mut process) = self.process.take() {
+            let _ = fs::remove_file(self.pid_path());
             process.kill()?;
             process.wait()?;
         }
         Ok(())
     }
 
-    pub async fn get_group(&self, group_name: &str) -> Result<Group, Box<dyn std::error::Error>> {
-        let url = format!("{}/group/{}", &self.external_url, group_name);
-        let client = Client::builder().timeout(Duration::from_secs(5)).build()?;
-        let response = client.get(url).send().await?;
-        let group = response.json::<Group>().await?;
-        Ok(group)
+    fn pid_path(&self) -> String {
+        Path::new(&self.test_path)
+            .join(PID_FILE_NAME)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// 上次运行被强制结束时内核可能仍在运行，根据进程号文件和端口占用找到并结束它
+    fn kill_stale_core(&self) {
+        let mut candidates = BTreeMap::new();
+        let pid_path = self.pid_path();
+        if let Ok(content) = fs::read_to_string(&pid_path) {
+            let _ = fs::remove_file(&pid_path);
+            if let Ok(pid) = content.trim().parse::<u32>() {
+                candidates.insert(pid, None);
+            }
+        }
+        for port in [self.external_port, self.mixed_port] {
+            // 端口可能仍由本进程占用着
+            for pid in port_owners(port)
+                .into_iter()
+                .filter(|pid| *pid != std::process::id())
+            {
+                candidates.insert(pid, Some(port));
+            }
+        }
+
+        for (pid, port) in candidates {
+            // 进程号可能已被其他进程复用，只结束以本测试目录启动的内核，不只看进程名
+            if !is_core_process(pid, &self.core_path, &self.test_path) {
+                if let Some(port) = port {
+                    warn!(
+                        "端口 {} 被进程 {} 占用，该进程不是残留的内核，不结束",
+                        port, pid
+                    );
+                }
+                continue;
+            }
+            warn!("发现上次运行残留的内核进程 {}，正在结束", pid);
+            if let Err(e) = kill_process(pid) {
+                warn!("结束残留内核进程 {} 失败, {}", pid, e);
+            }
+        }
+    }
+
+    /// 检查控制端口和代理端口是否被占用，残留内核刚被结束时等待端口释放
+    async fn ensure_ports_free(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let deadline = Instant::now() + PORT_RELEASE_TIMEOUT;
+        for port in [self.external_port, self.mixed_port] {
+            while port_in_use(port) {
+                if Instant::now() >= deadline {
+                    return Err(format!(
+                        "端口 {} 已被占用，请结束占用该端口的进程（可能是残留的内核）后重试",
+                        port
+                    )
+                    .into());
+                }
+                sleep(POLL_INTERVAL).await;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn controller(&self) -> &Controller {
+        &self.controller
+    }
+
+    pub async fn get_group(
+        &self,
+        group_name: &str,
+    ) -> Result<ProxyInfo, Box<dyn std::error::Error>> {
+        Ok(self.controller.group(group_name).await?)
     }
 
     pub async fn test_group(
@@ -97,34 +415,14 @@ impl ClashMeta {
         group_name: &str,
         delay_test_config: &DelayTestConfig,
     ) -> Result<HashMap<String, i64>, Box<dyn std::error::Error>> {
-        let url = format!("{}/group/{}/delay", &self.external_url, group_name);
-        let client = Client::builder()
-            .timeout(Duration::from_secs(10))
-            .build()
-            .unwrap();
-        let response = client.get(&url).query(&delay_test_config).send().await?;
-        if !response.status().is_success() {
-            return Err(Box::from("获取分组延迟失败".to_string()));
-        }
-        let res: Value = response.json().await?;
-        match res {
-            Value::Object(map) => {
-                let msg = map.get("message");
-                if msg.is_some() {
-                    let msg = msg.unwrap();
-                    Err(Box::from(msg.to_string()))
-                } else {
-                    let mut result = HashMap::new();
-                    for (name, value) in map {
-                        if let Some(num) = value.as_i64() {
-                            result.insert(name.clone(), num);
-                        }
-                    }
-                    Ok(result)
-                }
-            }
-            _ => Err(Box::from("所有节点无速度")),
+        let result = self
+            .controller
+            .group_delay(group_name, delay_test_config)
+            .await?;
+        if result.is_empty() {
+            return Err(Box::from("所有节点无速度"));
         }
+        Ok(result)
     }
 
     pub async fn test_proxy(
@@ -132,13 +430,10 @@ impl ClashMeta {
         proxy_name: &str,
         delay_test_config: &DelayTestConfig,
     ) -> Result<u64, Box<dyn std::error::Error>> {
-        let url = format!("{}/proxies/{}/delay", &self.external_url, proxy_name);
-        let client = Client::builder().timeout(Duration::from_secs(60)).build()?;
-        let response = client.get(&url).query(delay_test_config).send().await?;
-        if !response.status().is_success() {
-            return Err(Box::from("获取分组延迟失败".to_string()));
-        }
-        Ok(response.json::<ProxyDelay>().await?.delay)
+        Ok(self
+            .controller
+            .proxy_delay(proxy_name, delay_test_config)
+            .await?)
     }
 
     pub async fn test_direct_delay(&self) -> Result<u64, Box<dyn std::error::Error>> {
@@ -157,28 +452,159 @@ impl ClashMeta {
         &self,
         group_name: &str,
         proxy_name: &str,
-    ) -> Result<bool, Box<dyn std::error::Error>> {
-        let url = format!("{}/proxies/{}", &self.external_url, group_name);
-        let client = Client::builder().timeout(Duration::from_secs(5)).build()?;
-        let response = client
-            .put(url)
-            .json(&json!({"name": proxy_name}))
-            .send()
-            .await?;
-        Ok(response.status().is_success())
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(self.controller.select_proxy(group_name, proxy_name).await?)
     }
 }
 
-#[derive(Deserialize, Debug)]
-#[allow(unused)]
-struct ClashVersion {
-    meta: bool,
-    version: String,
+/// 将已下载的 geo 数据复制到新的工作目录，避免每个内核重复下载
+pub fn copy_geo_files(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let path = entry?.path();
+        let is_geo_file = path
+            .extension()
+            .is_some_and(|ext| ext == "dat" || ext == "mmdb");
+        if !path.is_file() || !is_geo_file {
+            continue;
+        }
+        let target = to.join(path.file_name().unwrap());
+        if !target.exists() {
+            fs::copy(&path, target)?;
+        }
+    }
+    Ok(())
+}
+
+impl Drop for ClashMeta {
+    fn drop(&mut self) {
+        if let Err(e) = self.shutdown() {
+            warn!("内核进程结束失败, {}", e);
+        }
+    }
+}
+
+fn port_in_use(port: u64) -> bool {
+    let Ok(port) = u16::try_from(port) else {
+        return false;
+    };
+    matches!(
+        TcpListener::bind(("127.0.0.1", port)),
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse
+    )
+}
+
+/// 进程的命令行包含内核程序名，且以 `-d test_path` 启动时才视为本程序的内核
+fn is_core_process(pid: u32, core_path: &str, test_path: &str) -> bool {
+    let core_name = Path::new(core_path)
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let Some(command_line) = process_command_line(pid) else {
+        return false;
+    };
+    let command_line = command_line.replace('"', "");
+    !core_name.is_empty()
+        && command_line.to_lowercase().contains(&core_name)
+        && command_line
+            .trim_end()
+            .ends_with(&format!("-d {}", test_path))
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct ProxyDelay {
-    pub delay: u64,
+#[cfg(unix)]
+fn process_command_line(pid: u32) -> Option<String> {
+    let output = Command::new("ps")
+        .args(["-p", &pid.to_string(), "-o", "command="])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(windows)]
+fn process_command_line(pid: u32) -> Option<String> {
+    let output = Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-Command",
+            &format!(
+                "(Get-CimInstance Win32_Process -Filter \"ProcessId={}\").CommandLine",
+                pid
+            ),
+        ])
+        .output()
+        .ok()?;
+    let command_line = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !command_line.is_empty()).then_some(command_line)
+}
+
+/// 监听该端口的进程号，查询失败时返回空
+#[cfg(unix)]
+fn port_owners(port: u64) -> Vec<u32> {
+    Command::new("lsof")
+        .args(["-nP", "-t", &format!("-iTCP:{}", port), "-sTCP:LISTEN"])
+        .output()
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter_map(|line| line.trim().parse().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(windows)]
+fn port_owners(port: u64) -> Vec<u32> {
+    let suffix = format!(":{}", port);
+    Command::new("netstat")
+        .args(["-ano", "-p", "TCP"])
+        .output()
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter_map(|line| {
+                    let columns: Vec<&str> = line.split_whitespace().collect();
+                    match columns.as_slice() {
+                        [_, local, _, "LISTENING", pid] if local.ends_with(&suffix) => {
+                            pid.parse().ok()
+                        }
+                        _ => None,
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(unix)]
+fn kill_process(pid: u32) -> io::Result<()> {
+    let status = Command::new("kill")
+        .args(["-9", &pid.to_string()])
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("kill 退出状态 {}", status)))
+    }
+}
+
+#[cfg(windows)]
+fn kill_process(pid: u32) -> io::Result<()> {
+    let status = Command::new("taskkill")
+        .args(["/F", "/PID", &pid.to_string()])
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("taskkill 退出状态 {}", status)))
+    }
+}
+
+fn tail_lines(content: &str, count: usize) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    lines[lines.len().saturating_sub(count)..].join("\n")
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -189,72 +615,299 @@ pub struct DelayTestConfig {
     pub timeout: u64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[allow(unused)]
-pub struct Group {
-    pub all: Vec<String>,
-    pub now: String,
-    pub name: String,
-}
-
 #[cfg(test)]
 mod tests {
+    use std::process::Command;
+
+    use crate::clash::copy_geo_files;
+    use crate::clash::port_in_use;
+    use crate::clash::process_command_line;
+    use crate::clash::tail_lines;
     use crate::clash::ClashMeta;
     use crate::clash::DelayTestConfig;
+    use crate::clash::PID_FILE_NAME;
+    use crate::clash::SECRET_LEN;
+    use crate::controller::Controller;
+    use crate::fake_controller::Delay;
+    use crate::fake_controller::FakeController;
+    use crate::fake_controller::Scenario;
+
+    #[test]
+    fn test_tail_lines() {
+        assert_eq!(tail_lines("a\nb\nc\n", 2), "b\nc");
+        assert_eq!(tail_lines("a", 5), "a");
+        assert_eq!(tail_lines("", 5), "");
+    }
+
+    #[tokio::test]
+    async fn test_start_detects_early_exit() {
+        let test_path = std::env::temp_dir().join("clash-butler-early-exit");
+        std::fs::create_dir_all(&test_path).unwrap();
+        std::fs::write(test_path.join("config.yaml"), "mode: rule\n").unwrap();
+        let mut clash_meta = ClashMeta::with_free_ports().unwrap();
+        clash_meta.core_path = "false".to_string();
+        clash_meta.test_path = test_path.to_string_lossy().to_string();
+        clash_meta.log_path = test_path.join("clash.log").to_string_lossy().to_string();
+        let err = clash_meta.start().await.unwrap_err().to_string();
+        assert!(err.contains("内核已退出"), "{}", err);
+        std::fs::remove_dir_all(test_path).unwrap();
+    }
+
+    #[test]
+    fn test_write_runtime_options() {
+        let test_path = std::env::temp_dir().join("clash-butler-runtime-options");
+        std::fs::create_dir_all(&test_path).unwrap();
+        let config_path = test_path.join("config.yaml");
+        std::fs::write(
+            &config_path,
+            "mixed-port: 7998\nmode: rule\nexternal-controller: \":9095\"\n",
+        )
+        .unwrap();
+        let mut clash_meta = ClashMeta::with_free_ports().unwrap();
+        clash_meta.test_path = test_path.to_string_lossy().to_string();
+        assert_ne!(clash_meta.external_port, clash_meta.mixed_port);
+        // 内核启动前端口保持占用
+        assert!(port_in_use(clash_meta.external_port));
+        assert!(port_in_use(clash_meta.mixed_port));
+        assert_eq!(clash_meta.secret.len(), SECRET_LEN);
+        clash_meta.write_runtime_options().unwrap();
+
+        let content = std::fs::read_to_string(&config_path).unwrap();
+        let config: serde_yaml::Value = serde_yaml::from_str(&content).unwrap();
+        assert_eq!(config["mixed-port"].as_u64(), Some(clash_meta.mixed_port));
+        assert_eq!(
+            config["external-controller"].as_str().unwrap(),
+            format!("127.0.0.1:{}", clash_meta.external_port)
+        );
+        assert_eq!(config["secret"].as_str().unwrap(), clash_meta.secret);
+        assert_eq!(config["mode"].as_str(), Some("rule"));
+        assert!(config.get("listeners").is_none());
+
+        let nodes = vec!["香港 01".to_string(), "日本 01".to_string()];
+        clash_meta.set_node_listeners(&nodes).unwrap();
+        clash_meta.write_runtime_options().unwrap();
+        let content = std::fs::read_to_string(&config_path).unwrap();
+        let config: serde_yaml::Value = serde_yaml::from_str(&content).unwrap();
+        let listeners = config["listeners"].as_sequence().unwrap();
+        assert_eq!(listeners.len(), 2);
+        for listener in listeners {
+            let node = listener["proxy"].as_str().unwrap();
+            let port = listener["port"].as_u64().unwrap();
+            assert_eq!(listener["type"].as_str(), Some("mixed"));
+            assert_eq!(
+                clash_meta.node_proxy_url(node),
+                Some(format!("http://127.0.0.1:{}", port))
+            );
+        }
+        assert_eq!(clash_meta.node_proxy_url("美国 01"), None);
+        std::fs::remove_dir_all(test_path).unwrap();
+    }
+
+    #[test]
+    fn test_copy_geo_files() {
+        let from = std::env::temp_dir().join("clash-butler-geo-from");
+        let to = std::env::temp_dir().join("clash-butler-geo-to");
+        std::fs::create_dir_all(&from).unwrap();
+        std::fs::write(from.join("geosite.dat"), "site").unwrap();
+        std::fs::write(from.join("country.mmdb"), "mmdb").unwrap();
+        std::fs::write(from.join("config.yaml"), "mode: rule").unwrap();
+        copy_geo_files(&from, &to).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(to.join("geosite.dat")).unwrap(),
+            "site"
+        );
+        assert!(to.join("country.mmdb").exists());
+        assert!(!to.join("config.yaml").exists());
+        std::fs::remove_dir_all(from).unwrap();
+        std::fs::remove_dir_all(to).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_drop_kills_core() {
+        let test_path = std::env::temp_dir().join("clash-butler-drop");
+        std::fs::create_dir_all(&test_path).unwrap();
+        let mut clash_meta = ClashMeta::new(1, 2);
+        clash_meta.test_path = test_path.to_string_lossy().to_string();
+        let child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id();
+        std::fs::write(clash_meta.pid_path(), pid.to_string()).unwrap();
+        clash_meta.process = Some(child);
+        drop(clash_meta);
+        assert!(process_command_line(pid).is_none());
+        assert!(!test_path.join(PID_FILE_NAME).exists());
+        std::fs::remove_dir_all(test_path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_kill_stale_core() {
+        let test_path =
+            std::env::temp_dir().join(format!("clash-butler-stale-{}", std::process::id()));
+        std::fs::create_dir_all(&test_path).unwrap();
+        let test_path_str = test_path.to_string_lossy().to_string();
+        // 用 sh 模拟以 `-d test_path` 启动的内核
+        let mut stale = Command::new("sh")
+            .args([
+                "-c",
+                "while :; do sleep 1; done",
+                "sh",
+                "-d",
+                &test_path_str,
+            ])
+            .spawn()
+            .unwrap();
+        let mut clash_meta = ClashMeta::new(1, 2);
+        clash_meta.test_path = test_path_str;
+        clash_meta.core_path = "sh".to_string();
+        std::fs::write(clash_meta.pid_path(), stale.id().to_string()).unwrap();
+        clash_meta.kill_stale_core();
+        assert!(!stale.wait().unwrap().success());
+        assert!(!test_path.join(PID_FILE_NAME).exists());
+        std::fs::remove_dir_all(test_path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_keep_process_with_same_name() {
+        let test_path =
+            std::env::temp_dir().join(format!("clash-butler-same-name-{}", std::process::id()));
+        std::fs::create_dir_all(&test_path).unwrap();
+        // 进程名与内核一致，但不是以本测试目录启动的
+        let mut other = Command::new("sleep").arg("30").spawn().unwrap();
+        let mut clash_meta = ClashMeta::new(1, 2);
+        clash_meta.test_path = test_path.to_string_lossy().to_string();
+        clash_meta.core_path = "sleep".to_string();
+        std::fs::write(clash_meta.pid_path(), other.id().to_string()).unwrap();
+        clash_meta.kill_stale_core();
+        assert!(other.try_wait().unwrap().is_none());
+        other.kill().unwrap();
+        other.wait().unwrap();
+        std::fs::remove_dir_all(test_path).unwrap();
+    }
+
+    fn delay_config(timeout: u64) -> DelayTestConfig {
+        DelayTestConfig {
+            url: "http://www.gstatic.com/generate_204".to_string(),
+            expected: Some(204),
+            timeout,
+        }
+    }
+
+    fn scenario() -> Scenario {
+        Scenario::new()
+            .with_group("PROXY", &["🇭🇰 HK 01", "JP/02", "US 03", "SG 04"])
+            .with_delay("🇭🇰 HK 01", Delay::Ok(120))
+            .with_delay("JP/02", Delay::Ok(80))
+            .with_delay("US 03", Delay::Timeout)
+            .with_delay(
+                "SG 04",
+                Delay::Error("dial tcp: connection refused".to_string()),
+            )
+            .with_delay("DIRECT", Delay::Ok(5))
+    }
 
     #[tokio::test]
-    #[ignore]
     async fn test_proxy_delay() {
-        let clash_meta = ClashMeta::new(9095, 7998);
+        let controller = FakeController::spawn(scenario()).await;
+        let clash_meta = ClashMeta::new(controller.port as u64, 0);
         let delay = clash_meta
-            .test_proxy(
-                "DIRECT",
-                &DelayTestConfig {
-                    url: "http://www.gstatic.com/generate_204".to_string(),
-                    expected: Some(204),
-                    timeout: 500,
-                },
-            )
+            .test_proxy("🇭🇰 HK 01", &delay_config(200))
             .await
             .unwrap();
-        println!("{}", delay);
+        assert_eq!(delay, 120);
+        assert_eq!(clash_meta.test_direct_delay().await.unwrap(), 5);
+
+        let err = clash_meta
+            .test_proxy("US 03", &delay_config(100))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Timeout"), "{}", err);
+        let err = clash_meta
+            .test_proxy("SG 04", &delay_config(100))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("connection refused"), "{}", err);
+        assert!(clash_meta
+            .test_proxy("missing", &delay_config(100))
+            .await
+            .is_err());
     }
 
     #[tokio::test]
-    #[ignore]
     async fn test_group_proxies() {
-        let clash_meta = ClashMeta::new(9095, 7998);
-        let result = clash_meta.get_group("PROXY").await;
-        println!("{:?}", result);
+        let controller = FakeController::spawn(scenario()).await;
+        let clash_meta = ClashMeta::new(controller.port as u64, 0);
+        let group = clash_meta.get_group("PROXY").await.unwrap();
+        assert_eq!(group.name, "PROXY");
+        assert_eq!(group.all.len(), 4);
+        assert_eq!(group.now, "🇭🇰 HK 01");
+        assert!(clash_meta.get_group("MISSING").await.is_err());
     }
 
     #[tokio::test]
-    #[ignore]
     async fn test_set_group_node() {
-        let clash_meta = ClashMeta::new(9095, 7998);
-        let result = clash_meta
+        let controller = FakeController::spawn(scenario()).await;
+        let clash_meta = ClashMeta::new(controller.port as u64, 0);
+        clash_meta.set_group_proxy("PROXY", "JP/02").await.unwrap();
+        assert_eq!(controller.selected("PROXY").as_deref(), Some("JP/02"));
+        assert_eq!(clash_meta.get_group("PROXY").await.unwrap().now, "JP/02");
+
+        let err = clash_meta
             .set_group_proxy("PROXY", "None_None_vmess_044")
-            .await;
-        if result.is_ok() {
-            println!("success")
-        }
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("proxy not exist"), "{}", err);
     }
 
     #[tokio::test]
-    #[ignore]
     async fn test_group_delay() {
-        let clash_meta = ClashMeta::new(9095, 7890);
+        let controller = FakeController::spawn(scenario()).await;
+        let clash_meta = ClashMeta::new(controller.port as u64, 0);
         let result = clash_meta
-            .test_group(
-                "PROXY",
-                &DelayTestConfig {
-                    url: "http://www.google.com/generate_204".to_string(),
-                    expected: Some(204),
-                    timeout: 1000,
-                },
-            )
-            .await;
+            .test_group("PROXY", &delay_config(100))
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result["🇭🇰 HK 01"], 120);
+        assert_eq!(result["JP/02"], 80);
 
-        println!("{:?}", result);
+        // 全部节点失败时返回错误
+        let controller = FakeController::spawn(
+            Scenario::new()
+                .with_group("PROXY", &["US 03"])
+                .with_delay("US 03", Delay::Timeout),
+        )
+        .await;
+        let clash_meta = ClashMeta::new(controller.port as u64, 0);
+        let err = clash_meta
+            .test_group("PROXY", &delay_config(100))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("all proxies timeout"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_secret() {
+        let controller = FakeController::spawn(scenario().with_secret("s3cret")).await;
+        let mut clash_meta = ClashMeta::new(controller.port as u64, 0);
+        let err = clash_meta.get_group("PROXY").await.unwrap_err();
+        assert!(err.to_string().contains("401"), "{}", err);
+
+        clash_meta.secret = "s3cret".to_string();
+        clash_meta.controller =
+            Controller::new(&clash_meta.external_url, &clash_meta.secret).unwrap();
+        assert!(clash_meta.get_group("PROXY").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_wait_ready() {
+        let controller = FakeController::spawn(scenario()).await;
+        let mut clash_meta = ClashMeta::new(controller.port as u64, 0);
+        let version = clash_meta
+            .wait_ready(std::time::Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(version.version, "fake");
     }
 }
@@ -0,0 +1,652 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use futures_util::stream;
+use futures_util::stream::BoxStream;
+use futures_util::Stream;
+use futures_util::StreamExt;
+use reqwest::header::HeaderMap;
+use reqwest::header::HeaderValue;
+use reqwest::header::AUTHORIZATION;
+use reqwest::Client;
+use reqwest::Method;
+use reqwest::RequestBuilder;
+use reqwest::Response;
+use reqwest::StatusCode;
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+
+use crate::clash::DelayTestConfig;
+
+// 普通请求的默认超时，流式接口不设超时
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub type Result<T> = std::result::Result<T, ControllerError>;
+
+#[derive(Debug)]
+pub enum ControllerError {
+    /// 控制接口地址或 secret 无效
+    Config(String),
+    /// 连接失败、超时等网络错误
+    Request(reqwest::Error),
+    /// 控制接口返回非 2xx 状态码，message 为响应中的错误信息
+    Status { status: StatusCode, message: String },
+    /// 响应内容无法解析
+    Decode(serde_json::Error),
+}
+
+impl fmt::Display for ControllerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControllerError::Config(msg) => write!(f, "控制接口配置有误: {}", msg),
+            ControllerError::Request(e) => write!(f, "请求控制接口失败: {}", e),
+            ControllerError::Status { status, message } => {
+                write!(f, "控制接口返回 {}: {}", status, message)
+            }
+            ControllerError::Decode(e) => write!(f, "解析控制接口响应失败: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ControllerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ControllerError::Request(e) => Some(e),
+            ControllerError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ControllerError {
+    fn from(e: reqwest::Error) -> Self {
+        ControllerError::Request(e)
+    }
+}
+
+impl From<serde_json::Error> for ControllerError {
+    fn from(e: serde_json::Error) -> Self {
+        ControllerError::Decode(e)
+    }
+}
+
+/// mihomo RESTful API 客户端，所有请求共用同一个连接池并携带 secret
+/// 封装了完整的控制接口，流程中暂未使用的接口单独标注了 dead_code
+#[derive(Clone, Debug)]
+pub struct Controller {
+    base_url: Url,
+    client: Client,
+    timeout: Duration,
+}
+
+impl Controller {
+    pub fn new(base_url: &str, secret: &str) -> Result<Self> {
+        let base_url = Url::parse(base_url)
+            .map_err(|e| ControllerError::Config(format!("{}: {}", base_url, e)))?;
+        let mut headers = HeaderMap::new();
+        if !secret.is_empty() {
+            let mut value = HeaderValue::from_str(&format!("Bearer {}", secret))
+                .map_err(|e| ControllerError::Config(format!("secret 无效: {}", e)))?;
+            value.set_sensitive(true);
+            headers.insert(AUTHORIZATION, value);
+        }
+        let client = Client::builder().default_headers(headers).build()?;
+        Ok(Controller {
+            base_url,
+            client,
+            timeout: DEFAULT_TIMEOUT,
+        })
+    }
+
+    /// 返回使用另一个默认超时的客户端，与原客户端共用连接池
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        Controller {
+            timeout,
+            ..self.clone()
+        }
+    }
+
+    /// 拼接接口路径，每一段都会进行百分号编码，节点名称中的空格、/ 和 emoji 不影响路径
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .expect("控制接口地址为 http URL")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    fn request(&self, method: Method, segments: &[&str]) -> RequestBuilder {
+        self.client
+            .request(method, self.url(segments))
+            .timeout(self.timeout)
+    }
+
+    async fn send(request: RequestBuilder) -> Result<Response> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        // mihomo 的错误响应为 {"message": "..."}
+        let text = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<Value>(&text)
+            .ok()
+            .and_then(|v| v.get("message").and_then(Value::as_str).map(str::to_string))
+            .unwrap_or(text);
+        Err(ControllerError::Status { status, message })
+    }
+
+    async fn send_json<T: DeserializeOwned>(request: RequestBuilder) -> Result<T> {
+        let bytes = Self::send(request).await?.bytes().await?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    async fn send_empty(request: RequestBuilder) -> Result<()> {
+        Self::send(request).await?;
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    fn stream<T>(&self, segments: &[&str], query: &[(&str, &str)]) -> BoxStream<'static, Result<T>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let request = self.client.get(self.url(segments)).query(query);
+        stream::once(Self::send(request))
+            .map(|response| match response {
+                Ok(response) => json_lines(Box::pin(response.bytes_stream())).boxed(),
+                Err(e) => stream::once(async { Err(e) }).boxed(),
+            })
+            .flatten()
+            .boxed()
+    }
+
+    pub async fn version(&self) -> Result<Version> {
+        Self::send_json(self.request(Method::GET, &["version"])).await
+    }
+
+    #[allow(dead_code)]
+    pub async fn proxies(&self) -> Result<HashMap<String, ProxyInfo>> {
+        let response: ProxiesResponse<HashMap<String, ProxyInfo>> =
+            Self::send_json(self.request(Method::GET, &["proxies"])).await?;
+        Ok(response.proxies)
+    }
+
+    #[allow(dead_code)]
+    pub async fn proxy(&self, name: &str) -> Result<ProxyInfo> {
+        Self::send_json(self.request(Method::GET, &["proxies", name])).await
+    }
+
+    /// 切换 select 分组当前选中的节点
+    pub async fn select_proxy(&self, group: &str, name: &str) -> Result<()> {
+        let request = self
+            .request(Method::PUT, &["proxies", group])
+            .json(&json!({ "name": name }));
+        Self::send_empty(request).await
+    }
+
+    pub async fn proxy_delay(&self, name: &str, config: &DelayTestConfig) -> Result<u64> {
+        let request = self
+            .request(Method::GET, &["proxies", name, "delay"])
+            .query(config)
+            .timeout(delay_timeout(config));
+        let delay: ProxyDelay = Self::send_json(request).await?;
+        Ok(delay.delay)
+    }
+
+    #[allow(dead_code)]
+    pub async fn groups(&self) -> Result<Vec<ProxyInfo>> {
+        let response: ProxiesResponse<Vec<ProxyInfo>> =
+            Self::send_json(self.request(Method::GET, &["group"])).await?;
+        Ok(response.proxies)
+    }
+
+    pub async fn group(&self, name: &str) -> Result<ProxyInfo> {
+        Self::send_json(self.request(Method::GET, &["group", name])).await
+    }
+
+    /// 测试分组内所有节点的延迟，返回节点名称到延迟的映射，超时的节点不在结果中
+    pub async fn group_delay(
+        &self,
+        name: &str,
+        config: &DelayTestConfig,
+    ) -> Result<HashMap<String, i64>> {
+        let request = self
+            .request(Method::GET, &["group", name, "delay"])
+            .query(config)
+            .timeout(delay_timeout(config));
+        let result: Map<String, Value> = Self::send_json(request).await?;
+        Ok(result
+            .into_iter()
+            .filter_map(|(name, delay)| delay.as_i64().map(|delay| (name, delay)))
+            .collect())
+    }
+
+    #[allow(dead_code)]
+    pub async fn proxy_providers(&self) -> Result<HashMap<String, ProxyProvider>> {
+        let response: ProvidersResponse<ProxyProvider> =
+            Self::send_json(self.request(Method::GET, &["providers", "proxies"])).await?;
+        Ok(response.providers)
+    }
+
+    #[allow(dead_code)]
+    pub async fn proxy_provider(&self, name: &str) -> Result<ProxyProvider> {
+        Self::send_json(self.request(Method::GET, &["providers", "proxies", name])).await
+    }
+
+    /// 重新拉取订阅
+    #[allow(dead_code)]
+    pub async fn update_proxy_provider(&self, name: &str) -> Result<()> {
+        Self::send_empty(self.request(Method::PUT, &["providers", "proxies", name])).await
+    }
+
+    #[allow(dead_code)]
+    pub async fn healthcheck_proxy_provider(&self, name: &str) -> Result<()> {
+        let request = self
+            .request(Method::GET, &["providers", "proxies", name, "healthcheck"])
+            .timeout(Duration::from_secs(60));
+        Self::send_empty(request).await
+    }
+
+    #[allow(dead_code)]
+    pub async fn healthcheck_provider_proxy(
+        &self,
+        provider: &str,
+        name: &str,
+        config: &DelayTestConfig,
+    ) -> Result<u64> {
+        let request = self
+            .request(
+                Method::GET,
+                &["providers", "proxies", provider, name, "healthcheck"],
+            )
+            .query(config)
+            .timeout(delay_timeout(config));
+        let delay: ProxyDelay = Self::send_json(request).await?;
+        Ok(delay.delay)
+    }
+
+    #[allow(dead_code)]
+    pub async fn rule_providers(&self) -> Result<HashMap<String, RuleProvider>> {
+        let response: ProvidersResponse<RuleProvider> =
+            Self::send_json(self.request(Method::GET, &["providers", "rules"])).await?;
+        Ok(response.providers)
+    }
+
+    #[allow(dead_code)]
+    pub async fn update_rule_provider(&self, name: &str) -> Result<()> {
+        Self::send_empty(self.request(Method::PUT, &["providers", "rules", name])).await
+    }
+
+    #[allow(dead_code)]
+    pub async fn connections(&self) -> Result<Connections> {
+        Self::send_json(self.request(Method::GET, &["connections"])).await
+    }
+
+    #[allow(dead_code)]
+    pub async fn close_connections(&self) -> Result<()> {
+        Self::send_empty(self.request(Method::DELETE, &["connections"])).await
+    }
+
+    #[allow(dead_code)]
+    pub async fn close_connection(&self, id: &str) -> Result<()> {
+        Self::send_empty(self.request(Method::DELETE, &["connections", id])).await
+    }
+
+    #[allow(dead_code)]
+    pub async fn rules(&self) -> Result<Vec<Rule>> {
+        let response: RulesResponse =
+            Self::send_json(self.request(Method::GET, &["rules"])).await?;
+        Ok(response.rules)
+    }
+
+    #[allow(dead_code)]
+    pub async fn configs(&self) -> Result<Configs> {
+        Self::send_json(self.request(Method::GET, &["configs"])).await
+    }
+
+    /// 修改运行中的部分配置，如 {"mode": "global"}
+    #[allow(dead_code)]
+    pub async fn patch_configs(&self, patch: &Value) -> Result<()> {
+        Self::send_empty(self.request(Method::PATCH, &["configs"]).json(patch)).await
+    }
+
+    /// 从配置文件重新加载全部配置，force 为 true 时即使端口等配置变化也会强制重载
+    pub async fn put_configs(&self, path: &str, force: bool) -> Result<()> {
+        let request = self
+            .request(Method::PUT, &["configs"])
+            .query(&[("force", force)])
+            .json(&json!({ "path": path, "payload": "" }));
+        Self::send_empty(request).await
+    }
+
+    pub async fn restart(&self, path: &str) -> Result<()> {
+        let request = self
+            .request(Method::POST, &["restart"])
+            .json(&json!({ "path": path, "payload": "" }));
+        Self::send_empty(request).await
+    }
+
+    /// 更新 GeoIP、GeoSite 等数据库
+    #[allow(dead_code)]
+    pub async fn update_geo(&self) -> Result<()> {
+        let request = self
+            .request(Method::POST, &["configs", "geo"])
+            .timeout(Duration::from_secs(120));
+        Self::send_empty(request).await
+    }
+
+    /// 通过内核的 DNS 解析域名，record_type 如 A、AAAA
+    #[allow(dead_code)]
+    pub async fn dns_query(&self, name: &str, record_type: &str) -> Result<DnsResponse> {
+        let request = self
+            .request(Method::GET, &["dns", "query"])
+            .query(&[("name", name), ("type", record_type)]);
+        Self::send_json(request).await
+    }
+
+    /// 每秒推送一次的上下行速率
+    #[allow(dead_code)]
+    pub fn traffic(&self) -> BoxStream<'static, Result<Traffic>> {
+        self.stream(&["traffic"], &[])
+    }
+
+    /// 每秒推送一次的内存占用
+    #[allow(dead_code)]
+    pub fn memory(&self) -> BoxStream<'static, Result<Memory>> {
+        self.stream(&["memory"], &[])
+    }
+
+    /// 内核日志，level 可选 debug、info、warning、error、silent
+    #[allow(dead_code)]
+    pub fn logs(&self, level: &str) -> BoxStream<'static, Result<LogEntry>> {
+        self.stream(&["logs"], &[("level", level)])
+    }
+}
+
+// 延迟测试由内核计时，请求超时需要在测试超时的基础上留出余量
+fn delay_timeout(config: &DelayTestConfig) -> Duration {
+    Duration::from_millis(config.timeout) + DEFAULT_TIMEOUT
+}
+
+/// 将按行分隔的 JSON 响应体解析为流，流式接口每行推送一个对象
+#[allow(dead_code)]
+fn json_lines<T, S, B>(body: S) -> impl Stream<Item = Result<T>>
+where
+    T: DeserializeOwned,
+    S: Stream<Item = reqwest::Result<B>> + Unpin,
+    B: AsRef<[u8]>,
+{
+    stream::unfold(
+        (body, Vec::new(), false),
+        |(mut body, mut buffer, mut done)| async move {
+            loop {
+                if let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                    let line = buffer.drain(..=pos).collect::<Vec<u8>>();
+                    if line.trim_ascii().is_empty() {
+                        continue;
+                    }
+                    let item = serde_json::from_slice(line.trim_ascii()).map_err(Into::into);
+                    return Some((item, (body, buffer, done)));
+                }
+                if done {
+                    // 响应结束时最后一行可能没有换行符
+                    if buffer.trim_ascii().is_empty() {
+                        return None;
+                    }
+                    let item = serde_json::from_slice(buffer.trim_ascii()).map_err(Into::into);
+                    return Some((item, (body, Vec::new(), done)));
+                }
+                match body.next().await {
+                    Some(Ok(chunk)) => buffer.extend_from_slice(chunk.as_ref()),
+                    Some(Err(e)) => return Some((Err(e.into()), (body, Vec::new(), true))),
+                    None => done = true,
+                }
+            }
+        },
+    )
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct ProxiesResponse<T> {
+    proxies: T,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct ProvidersResponse<T> {
+    providers: HashMap<String, T>,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct RulesResponse {
+    rules: Vec<Rule>,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(unused)]
+pub struct Version {
+    #[serde(default)]
+    pub meta: bool,
+    pub version: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProxyDelay {
+    pub delay: u64,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct DelayHistory {
+    pub time: String,
+    pub delay: u64,
+}
+
+/// 节点或分组，分组才有 all 和 now
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct ProxyInfo {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub proxy_type: String,
+    pub alive: bool,
+    pub udp: bool,
+    pub history: Vec<DelayHistory>,
+    pub all: Vec<String>,
+    pub now: String,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, rename_all = "camelCase")]
+#[allow(dead_code)]
+pub struct SubscriptionInfo {
+    pub upload: u64,
+    pub download: u64,
+    pub total: u64,
+    pub expire: u64,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, rename_all = "camelCase")]
+#[allow(dead_code)]
+pub struct ProxyProvider {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub provider_type: String,
+    pub vehicle_type: String,
+    pub proxies: Vec<ProxyInfo>,
+    pub updated_at: Option<String>,
+    pub subscription_info: Option<SubscriptionInfo>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, rename_all = "camelCase")]
+#[allow(dead_code)]
+pub struct RuleProvider {
+    pub name: String,
+    pub behavior: String,
+    pub format: String,
+    pub rule_count: u64,
+    pub vehicle_type: String,
+    pub updated_at: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+#[allow(dead_code)]
+pub struct Rule {
+    #[serde(rename = "type")]
+    pub rule_type: String,
+    pub payload: String,
+    pub proxy: String,
+    pub size: i64,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, rename_all = "camelCase")]
+#[allow(dead_code)]
+pub struct Connections {
+    pub download_total: u64,
+    pub upload_total: u64,
+    pub connections: Vec<Connection>,
+    pub memory: u64,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+#[allow(dead_code)]
+pub struct Connection {
+    pub id: String,
+    pub upload: u64,
+    pub download: u64,
+    pub start: String,
+    pub chains: Vec<String>,
+    pub rule: String,
+    #[serde(rename = "rulePayload")]
+    pub rule_payload: String,
+    pub metadata: Map<String, Value>,
+}
+
+/// 常用的运行配置，其余字段保留在 extra 中
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, rename_all = "kebab-case")]
+#[allow(dead_code)]
+pub struct Configs {
+    pub port: u16,
+    pub socks_port: u16,
+    pub mixed_port: u16,
+    pub allow_lan: bool,
+    pub bind_address: String,
+    pub mode: String,
+    pub log_level: String,
+    pub ipv6: bool,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, rename_all = "PascalCase")]
+#[allow(dead_code)]
+pub struct DnsResponse {
+    pub status: i32,
+    pub question: Vec<DnsQuestion>,
+    pub answer: Vec<DnsAnswer>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+#[allow(dead_code)]
+pub struct DnsQuestion {
+    pub name: String,
+    #[serde(rename = "Qtype")]
+    pub qtype: u16,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+#[allow(dead_code)]
+pub struct DnsAnswer {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub record_type: u16,
+    #[serde(rename = "TTL")]
+    pub ttl: u32,
+    pub data: String,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+#[allow(dead_code)]
+pub struct Traffic {
+    pub up: u64,
+    pub down: u64,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+#[allow(dead_code)]
+pub struct Memory {
+    pub inuse: u64,
+    pub oslimit: u64,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+#[allow(dead_code)]
+pub struct LogEntry {
+    #[serde(rename = "type")]
+    pub level: String,
+    pub payload: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::stream;
+    use futures_util::StreamExt;
+
+    use crate::controller::json_lines;
+    use crate::controller::Controller;
+    use crate::controller::Result;
+    use crate::controller::Traffic;
+
+    #[test]
+    fn test_url_encodes_segments() {
+        let controller = Controller::new("http://127.0.0.1:9090", "").unwrap();
+        let url = controller.url(&["proxies", "🇭🇰 HK/01 #a", "delay"]);
+        assert_eq!(
+            url.as_str(),
+            "http://127.0.0.1:9090/proxies/%F0%9F%87%AD%F0%9F%87%B0%20HK%2F01%20%23a/delay"
+        );
+    }
+
+    #[test]
+    fn test_invalid_base_url() {
+        assert!(Controller::new("127.0.0.1:9090 x", "").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_json_lines() {
+        let chunks = vec![
+            Ok::<_, reqwest::Error>(b"{\"up\":1,\"do".to_vec()),
+            Ok(b"wn\":2}\n\n{\"up\":3,".to_vec()),
+            Ok(b"\"down\":4}".to_vec()),
+        ];
+        let items = json_lines::<Traffic, _, _>(stream::iter(chunks))
+            .collect::<Vec<Result<Traffic>>>()
+            .await;
+        let items = items.into_iter().map(|t| t.unwrap()).collect::<Vec<_>>();
+        assert_eq!(items.len(), 2);
+        assert_eq!((items[0].up, items[0].down), (1, 2));
+        assert_eq!((items[1].up, items[1].down), (3, 4));
+    }
+}
@@ -0,0 +1,268 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+// 报告中保留的日志消息最大长度
+const MAX_MESSAGE_LEN: usize = 300;
+
+/// 节点失败的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureStage {
+    // 连通性测试
+    Connect,
+    // 重命名时的出口 IP 和 AI 服务检测
+    Probe,
+}
+
+/// 按内核日志归类的失败原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureCategory {
+    Tls,
+    Auth,
+    Timeout,
+    Refused,
+    Reset,
+    Dns,
+    Unsupported,
+    // 连通性测试无延迟且内核日志中没有相关记录
+    NoDelay,
+    // 节点可用但 Gemini 和 Claude 均不可用
+    Unlock,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NodeFailure {
+    pub stage: FailureStage,
+    pub category: FailureCategory,
+    // 最后一条相关的日志消息
+    pub message: String,
+    // 内核日志中与该节点相关的错误条数
+    pub occurrences: usize,
+}
+
+impl NodeFailure {
+    pub fn new(stage: FailureStage, category: FailureCategory, message: &str) -> Self {
+        NodeFailure {
+            stage,
+            category,
+            message: truncate(message),
+            occurrences: 0,
+        }
+    }
+}
+
+/// 根据错误消息中的关键字归类
+pub fn classify(message: &str) -> FailureCategory {
+    let message = message.to_lowercase();
+    let contains_any = |keys: &[&str]| keys.iter().any(|key| message.contains(key));
+    if contains_any(&["tls", "x509", "certificate", "reality", "handshake"]) {
+        FailureCategory::Tls
+    } else if contains_any(&["auth", "unauthorized", "invalid user", "password", "407"]) {
+        FailureCategory::Auth
+    } else if contains_any(&["cipher", "unsupported", "not supported", "unknown method"]) {
+        FailureCategory::Unsupported
+    } else if contains_any(&["timeout", "timed out", "deadline exceeded"]) {
+        FailureCategory::Timeout
+    } else if contains_any(&["connection refused", "refused"]) {
+        FailureCategory::Refused
+    } else if contains_any(&["connection reset", "broken pipe", "eof"]) {
+        FailureCategory::Reset
+    } else if contains_any(&["no such host", "dns", "resolve"]) {
+        FailureCategory::Dns
+    } else {
+        FailureCategory::Unknown
+    }
+}
+
+/// 从内核日志中找出与失败节点相关的错误，按节点汇总并归类
+///
+/// 只统计 warning 和 error 级别的日志，一行日志匹配多个节点名称时归属最长的名称，
+/// 避免「HK 1」误匹配「HK 10」的日志
+pub fn diagnose(log: &str, nodes: &[String], stage: FailureStage) -> BTreeMap<String, NodeFailure> {
+    let mut counts: HashMap<&str, HashMap<FailureCategory, usize>> = HashMap::new();
+    let mut last_messages: HashMap<&str, String> = HashMap::new();
+    for line in log.lines() {
+        if !line.contains("level=warning") && !line.contains("level=error") {
+            continue;
+        }
+        let Some(message) = log_message(line) else {
+            continue;
+        };
+        let Some(node) = nodes
+            .iter()
+            .filter(|node| mentions(&message, node))
+            .max_by_key(|node| node.len())
+        else {
+            continue;
+        };
+        *counts
+            .entry(node)
+            .or_default()
+            .entry(classify(&message))
+            .or_default() += 1;
+        last_messages.insert(node, message);
+    }
+
+    counts
+        .into_iter()
+        .map(|(node, categories)| {
+            let occurrences = categories.values().sum();
+            // 取出现次数最多的类别，次数相同时优先取具体的类别，再按声明顺序取靠前的类别
+            let category = categories
+                .into_iter()
+                .max_by_key(|(category, count)| {
+                    (
+                        *count,
+                        *category != FailureCategory::Unknown,
+                        Reverse(*category),
+                    )
+                })
+                .map(|(category, _)| category)
+                .unwrap_or(FailureCategory::Unknown);
+            let failure = NodeFailure {
+                stage,
+                category,
+                message: truncate(&last_messages[node]),
+                occurrences,
+            };
+            (node.to_string(), failure)
+        })
+        .collect()
+}
+
+/// 用内核日志中的原因替换兜底原因，AI 服务不可用的节点本身可以连通，保留原有原因
+pub fn with_log_reasons(
+    log: &str,
+    mut failures: BTreeMap<String, NodeFailure>,
+    stage: FailureStage,
+) -> BTreeMap<String, NodeFailure> {
+    let nodes = failures
+        .iter()
+        .filter(|(_, failure)| failure.category != FailureCategory::Unlock)
+        .map(|(node, _)| node.clone())
+        .collect::<Vec<String>>();
+    failures.extend(diagnose(log, &nodes, stage));
+    failures
+}
+
+// mihomo 日志格式为 time="..." level=warning msg="..."
+fn log_message(line: &str) -> Option<String> {
+    let start = line.find("msg=\"")? + "msg=\"".len();
+    let end = line.rfind('"').filter(|end| *end >= start)?;
+    Some(line[start..end].replace("\\\"", "\"").replace("\\\\", "\\"))
+}
+
+// 名称前后需要是边界字符，避免匹配到更长名称的一部分
+fn mentions(message: &str, node: &str) -> bool {
+    let is_boundary = |c: Option<char>| {
+        c.is_none_or(|c| c.is_whitespace() || matches!(c, '[' | ']' | '(' | ')' | ':' | '"' | ','))
+    };
+    message.match_indices(node).any(|(index, _)| {
+        is_boundary(message[..index].chars().next_back())
+            && is_boundary(message[index + node.len()..].chars().next())
+    })
+}
+
+fn truncate(message: &str) -> String {
+    if message.chars().count() <= MAX_MESSAGE_LEN {
+        message.to_string()
+    } else {
+        let mut message = message.chars().take(MAX_MESSAGE_LEN).collect::<String>();
+        message.push('…');
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::diagnosis::classify;
+    use crate::diagnosis::diagnose;
+    use crate::diagnosis::with_log_reasons;
+    use crate::diagnosis::FailureCategory;
+    use crate::diagnosis::FailureStage;
+    use crate::diagnosis::NodeFailure;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            classify("remote error: tls: handshake failure"),
+            FailureCategory::Tls
+        );
+        assert_eq!(
+            classify("dial tcp 1.2.3.4:443: i/o timeout"),
+            FailureCategory::Timeout
+        );
+        assert_eq!(
+            classify("trojan: authentication failed"),
+            FailureCategory::Auth
+        );
+        assert_eq!(
+            classify("ss: unsupported cipher chacha20"),
+            FailureCategory::Unsupported
+        );
+        assert_eq!(
+            classify("connect: connection refused"),
+            FailureCategory::Refused
+        );
+        assert_eq!(classify("lookup a.com: no such host"), FailureCategory::Dns);
+        assert_eq!(classify("something else"), FailureCategory::Unknown);
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let log = r#"time="2025-01-01T00:00:00Z" level=info msg="Start initial configuration in progress"
+time="2025-01-01T00:00:01Z" level=warning msg="[TCP] dial HK 10 (match Match/) 127.0.0.1:5000 --> www.google.com:80 error: remote error: tls: handshake failure"
+time="2025-01-01T00:00:02Z" level=warning msg="[TCP] dial HK 1 (match Match/) 127.0.0.1:5001 --> www.google.com:80 error: dial tcp 1.2.3.4:443: i/o timeout"
+time="2025-01-01T00:00:03Z" level=warning msg="[TCP] dial HK 1 (match Match/) 127.0.0.1:5002 --> www.google.com:80 error: \"dial tcp 1.2.3.4:443: i/o timeout\""
+time="2025-01-01T00:00:04Z" level=debug msg="[TCP] dial JP 1 error: connection refused"
+"#;
+        let nodes = vec!["HK 1".to_string(), "HK 10".to_string(), "JP 1".to_string()];
+        let failures = diagnose(log, &nodes, FailureStage::Connect);
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures["HK 1"].category, FailureCategory::Timeout);
+        assert_eq!(failures["HK 1"].occurrences, 2);
+        assert!(failures["HK 1"]
+            .message
+            .ends_with("\"dial tcp 1.2.3.4:443: i/o timeout\""));
+        assert_eq!(failures["HK 10"].category, FailureCategory::Tls);
+        assert_eq!(failures["HK 10"].stage, FailureStage::Connect);
+        assert!(!failures.contains_key("JP 1"));
+    }
+
+    #[test]
+    fn test_with_log_reasons() {
+        let log = r#"time="2025-01-01T00:00:01Z" level=warning msg="[TCP] dial US 1 --> claude.ai:443 error: connection refused"
+time="2025-01-01T00:00:02Z" level=warning msg="[TCP] dial US 2 --> claude.ai:443 error: connection refused"
+"#;
+        let failures = BTreeMap::from([
+            (
+                "US 1".to_string(),
+                NodeFailure::new(
+                    FailureStage::Probe,
+                    FailureCategory::Unknown,
+                    "获取出口 IP 失败",
+                ),
+            ),
+            (
+                "US 2".to_string(),
+                NodeFailure::new(
+                    FailureStage::Probe,
+                    FailureCategory::Unlock,
+                    "Gemini 和 Claude 均不可用",
+                ),
+            ),
+        ]);
+        let failures = with_log_reasons(log, failures, FailureStage::Probe);
+        assert_eq!(failures["US 1"].category, FailureCategory::Refused);
+        assert_eq!(failures["US 1"].occurrences, 1);
+        assert_eq!(failures["US 2"].category, FailureCategory::Unlock);
+        assert_eq!(failures["US 2"].occurrences, 0);
+    }
+}
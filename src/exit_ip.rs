@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use crate::report::ExitIpAlias;
+
+/// 按出口 IP 对节点分组，每个出口只保留延迟最低的 keep 个节点
+/// 没有出口 IP 的节点原样保留，没有延迟数据的节点排在最后，keep 为 0 时不去重
+/// 返回保留的节点（保持原有顺序）以及被合并的别名记录
+pub fn dedup_by_exit_ip(
+    nodes: &[String],
+    exit_ips: &HashMap<String, IpAddr>,
+    delays: &HashMap<String, i64>,
+    keep: usize,
+) -> (Vec<String>, Vec<ExitIpAlias>) {
+    if keep == 0 {
+        return (nodes.to_vec(), vec![]);
+    }
+    let mut groups: BTreeMap<IpAddr, Vec<&String>> = BTreeMap::new();
+    for node in nodes {
+        if let Some(ip) = exit_ips.get(node) {
+            groups.entry(*ip).or_default().push(node);
+        }
+    }
+
+    let mut collapsed_nodes = Vec::new();
+    let mut aliases = Vec::new();
+    for (ip, mut members) in groups {
+        if members.len() <= keep {
+            continue;
+        }
+        // 稳定排序，延迟相同时保持原有顺序
+        members.sort_by_key(|node| delays.get(*node).copied().unwrap_or(i64::MAX));
+        let (kept, collapsed) = members.split_at(keep);
+        collapsed_nodes.extend(collapsed.iter().map(|n| n.to_string()));
+        aliases.push(ExitIpAlias {
+            ip: ip.to_string(),
+            kept: kept.iter().map(|n| n.to_string()).collect(),
+            collapsed: collapsed.iter().map(|n| n.to_string()).collect(),
+        });
+    }
+
+    let kept = nodes
+        .iter()
+        .filter(|node| !collapsed_nodes.contains(node))
+        .cloned()
+        .collect();
+    (kept, aliases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_dedup_by_exit_ip() {
+        let nodes = names(&["a", "b", "c", "d", "e"]);
+        let ip1: IpAddr = "1.1.1.1".parse().unwrap();
+        let ip2: IpAddr = "2.2.2.2".parse().unwrap();
+        let exit_ips = HashMap::from([
+            ("a".to_string(), ip1),
+            ("b".to_string(), ip1),
+            ("c".to_string(), ip1),
+            ("d".to_string(), ip2),
+        ]);
+        let delays = HashMap::from([
+            ("a".to_string(), 300),
+            ("b".to_string(), 100),
+            ("d".to_string(), 50),
+        ]);
+
+        let (kept, aliases) = dedup_by_exit_ip(&nodes, &exit_ips, &delays, 1);
+        assert_eq!(kept, names(&["b", "d", "e"]));
+        assert_eq!(
+            aliases,
+            vec![ExitIpAlias {
+                ip: "1.1.1.1".to_string(),
+                kept: names(&["b"]),
+                collapsed: names(&["a", "c"]),
+            }]
+        );
+
+        let (kept, aliases) = dedup_by_exit_ip(&nodes, &exit_ips, &delays, 2);
+        assert_eq!(kept, names(&["a", "b", "d", "e"]));
+        assert_eq!(aliases[0].collapsed, names(&["c"]));
+
+        let (kept, aliases) = dedup_by_exit_ip(&nodes, &exit_ips, &delays, 0);
+        assert_eq!(kept, nodes);
+        assert!(aliases.is_empty());
+    }
+}
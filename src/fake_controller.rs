@@ -0,0 +1,240 @@
+//! 测试用的 mihomo 控制接口，按脚本返回延迟、超时和错误信息
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::Request;
+use axum::extract::State;
+use axum::http::header::AUTHORIZATION;
+use axum::http::StatusCode;
+use axum::middleware;
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::routing::get;
+use axum::Json;
+use axum::Router;
+use serde::Deserialize;
+use serde_json::json;
+use serde_json::Value;
+use tokio::net::TcpListener;
+use tokio::time::sleep;
+
+/// 单个节点延迟测试的脚本结果
+#[derive(Debug, Clone)]
+pub enum Delay {
+    Ok(u64),
+    // 等待请求中的 timeout 后返回 504
+    Timeout,
+    // 立即返回 503 和错误信息
+    Error(String),
+}
+
+/// 控制接口的脚本场景
+#[derive(Debug, Clone, Default)]
+pub struct Scenario {
+    secret: String,
+    groups: HashMap<String, Vec<String>>,
+    delays: HashMap<String, Delay>,
+}
+
+impl Scenario {
+    pub fn new() -> Self {
+        Scenario::default()
+    }
+
+    pub fn with_secret(mut self, secret: &str) -> Self {
+        self.secret = secret.to_string();
+        self
+    }
+
+    pub fn with_group(mut self, name: &str, proxies: &[&str]) -> Self {
+        let proxies = proxies.iter().map(|p| p.to_string()).collect();
+        self.groups.insert(name.to_string(), proxies);
+        self
+    }
+
+    pub fn with_delay(mut self, proxy: &str, delay: Delay) -> Self {
+        self.delays.insert(proxy.to_string(), delay);
+        self
+    }
+}
+
+#[derive(Default)]
+struct FakeState {
+    scenario: Scenario,
+    // 分组当前选中的节点
+    selected: HashMap<String, String>,
+}
+
+type SharedState = Arc<Mutex<FakeState>>;
+
+/// 正在运行的控制接口，可以查看测试过程中产生的状态
+pub struct FakeController {
+    pub port: u16,
+    state: SharedState,
+}
+
+impl FakeController {
+    /// 在随机端口上启动控制接口
+    pub async fn spawn(scenario: Scenario) -> Self {
+        let state = Arc::new(Mutex::new(FakeState {
+            scenario,
+            selected: HashMap::new(),
+        }));
+        let app = Router::new()
+            .route("/version", get(version))
+            .route("/group/{name}", get(group))
+            .route("/group/{name}/delay", get(group_delay))
+            .route("/proxies/{name}", get(proxy).put(select_proxy))
+            .route("/proxies/{name}/delay", get(proxy_delay))
+            .layer(middleware::from_fn_with_state(state.clone(), authorize))
+            .with_state(state.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        FakeController { port, state }
+    }
+
+    pub fn selected(&self, group: &str) -> Option<String> {
+        self.state.lock().unwrap().selected.get(group).cloned()
+    }
+}
+
+#[derive(Deserialize)]
+struct DelayQuery {
+    timeout: u64,
+}
+
+#[derive(Deserialize)]
+struct SelectBody {
+    name: String,
+}
+
+fn message(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "message": message }))).into_response()
+}
+
+async fn authorize(State(state): State<SharedState>, request: Request, next: Next) -> Response {
+    let secret = state.lock().unwrap().scenario.secret.clone();
+    if !secret.is_empty() {
+        let expected = format!("Bearer {}", secret);
+        let authorized = request
+            .headers()
+            .get(AUTHORIZATION)
+            .is_some_and(|value| value.as_bytes() == expected.as_bytes());
+        if !authorized {
+            return message(StatusCode::UNAUTHORIZED, "Unauthorized");
+        }
+    }
+    next.run(request).await
+}
+
+async fn version() -> Json<Value> {
+    Json(json!({ "meta": true, "version": "fake" }))
+}
+
+fn group_json(state: &FakeState, name: &str) -> Option<Value> {
+    let proxies = state.scenario.groups.get(name)?;
+    let now = state
+        .selected
+        .get(name)
+        .or(proxies.first())
+        .cloned()
+        .unwrap_or_default();
+    Some(json!({ "name": name, "type": "Selector", "all": proxies, "now": now }))
+}
+
+async fn group(State(state): State<SharedState>, Path(name): Path<String>) -> Response {
+    match group_json(&state.lock().unwrap(), &name) {
+        Some(group) => Json(group).into_response(),
+        None => message(StatusCode::NOT_FOUND, "resource not found"),
+    }
+}
+
+async fn proxy(State(state): State<SharedState>, Path(name): Path<String>) -> Response {
+    let state = state.lock().unwrap();
+    if let Some(group) = group_json(&state, &name) {
+        return Json(group).into_response();
+    }
+    if state.scenario.delays.contains_key(&name) {
+        return Json(json!({ "name": name, "type": "Shadowsocks", "alive": true, "history": [] }))
+            .into_response();
+    }
+    message(StatusCode::NOT_FOUND, "resource not found")
+}
+
+async fn select_proxy(
+    State(state): State<SharedState>,
+    Path(name): Path<String>,
+    Json(body): Json<SelectBody>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    let Some(proxies) = state.scenario.groups.get(&name) else {
+        return message(StatusCode::NOT_FOUND, "resource not found");
+    };
+    if !proxies.contains(&body.name) {
+        return message(
+            StatusCode::BAD_REQUEST,
+            "Selector update error: proxy not exist",
+        );
+    }
+    state.selected.insert(name, body.name);
+    StatusCode::NO_CONTENT.into_response()
+}
+
+async fn proxy_delay(
+    State(state): State<SharedState>,
+    Path(name): Path<String>,
+    Query(query): Query<DelayQuery>,
+) -> Response {
+    let delay = state.lock().unwrap().scenario.delays.get(&name).cloned();
+    match delay {
+        Some(Delay::Ok(delay)) => Json(json!({ "delay": delay })).into_response(),
+        Some(Delay::Timeout) => {
+            sleep(Duration::from_millis(query.timeout)).await;
+            message(StatusCode::GATEWAY_TIMEOUT, "Timeout")
+        }
+        Some(Delay::Error(msg)) => message(StatusCode::SERVICE_UNAVAILABLE, &msg),
+        None => message(StatusCode::NOT_FOUND, "resource not found"),
+    }
+}
+
+/// 与 mihomo 一致，只返回有延迟的节点，全部失败时返回 504
+async fn group_delay(
+    State(state): State<SharedState>,
+    Path(name): Path<String>,
+    Query(query): Query<DelayQuery>,
+) -> Response {
+    let (proxies, delays) = {
+        let state = state.lock().unwrap();
+        let Some(proxies) = state.scenario.groups.get(&name) else {
+            return message(StatusCode::NOT_FOUND, "resource not found");
+        };
+        (proxies.clone(), state.scenario.delays.clone())
+    };
+    let result = proxies
+        .iter()
+        .filter_map(|proxy| match delays.get(proxy) {
+            Some(Delay::Ok(delay)) => Some((proxy.clone(), json!(delay))),
+            _ => None,
+        })
+        .collect::<serde_json::Map<String, Value>>();
+    if proxies
+        .iter()
+        .any(|proxy| matches!(delays.get(proxy), Some(Delay::Timeout)))
+    {
+        sleep(Duration::from_millis(query.timeout)).await;
+    }
+    if result.is_empty() {
+        return message(
+            StatusCode::GATEWAY_TIMEOUT,
+            "get delay: all proxies timeout",
+        );
+    }
+    Json(Value::Object(result)).into_response()
+}
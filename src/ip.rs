@@ -78,10 +78,18 @@ pub async fn get_ip_detail_from_ipapi(
         region: ip_api_detail.region_name,
         region_code: ip_api_detail.region,
         timezone: ip_api_detail.timezone,
+        // 格式如 AS13335 Cloudflare, Inc.
+        asn: ip_api_detail
+            .as_name
+            .split_whitespace()
+            .next()
+            .and_then(|asn| asn.trim_start_matches("AS").parse().ok()),
     })
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// 部分 IP 查询结果缺少城市等字段，缺失时为空
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct IpDetail {
     pub ip: String,
     pub country: String,
@@ -91,6 +99,7 @@ pub struct IpDetail {
     pub region: String,
     pub region_code: String,
     pub timezone: String,
+    pub asn: Option<u32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -105,6 +114,8 @@ pub struct IpApiDetail {
     #[serde(rename = "regionName")]
     pub region_name: String,
     pub timezone: String,
+    #[serde(rename = "as", default)]
+    pub as_name: String,
 }
 
 #[cfg(test)]
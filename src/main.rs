@@ -1,30 +1,71 @@
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::env;
 use std::fs;
+use std::net::IpAddr;
 use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Mutex;
 use std::time::Duration;
 
 use clap::Parser;
+use clap::Subcommand;
+use futures_util::future::join_all;
+use futures_util::stream;
+use futures_util::StreamExt;
 use proxrs::protocol::Proxy;
 use proxrs::sub::SubManager;
+use proxrs::transform;
 use tracing::error;
 use tracing::info;
+use tracing::warn;
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
 
+use crate::backend::MihomoBackend;
+use crate::backend::TestBackend;
+use crate::clash::copy_geo_files;
 use crate::clash::ClashMeta;
 use crate::clash::DelayTestConfig;
+use crate::diagnosis::FailureCategory;
+use crate::diagnosis::FailureStage;
+use crate::diagnosis::NodeFailure;
+use crate::names::NameRegistry;
+use crate::release::NodeMetrics;
+use crate::rename::NameTemplate;
+use crate::report::RunReport;
+use crate::safety::SafetyConfig;
 use crate::settings::Settings;
+use crate::stats::SourceStats;
+use crate::sticky::StickyConfig;
+use crate::sticky::StickyState;
 
+mod backend;
 mod cgi_trace;
 mod clash;
+mod controller;
+mod diagnosis;
+mod exit_ip;
+#[cfg(test)]
+mod fake_controller;
 mod ip;
+#[cfg(test)]
+mod mock_backend;
+mod names;
+mod release;
+mod rename;
+mod report;
 mod risk;
 mod routes;
+mod safety;
 mod server;
 mod settings;
 mod speedtest;
+mod stats;
+mod sticky;
+mod store;
 mod website;
 
 #[derive(Parser)]
@@ -33,12 +74,48 @@ struct Cli {
     // Starts the Axum server
     #[arg(long)]
     server: bool,
+    // Publish even if the safety thresholds are not met
+    #[arg(long)]
+    force: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage release snapshots under subs/release
+    Release {
+        #[command(subcommand)]
+        action: ReleaseAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReleaseAction {
+    /// List all snapshots
+    List,
+    /// Diff two snapshots by node fingerprint, TO defaults to the current clash.yaml
+    Diff { from: String, to: Option<String> },
+    /// Restore a snapshot as the current clash.yaml
+    Restore { id: String },
 }
 
-const TEST_PROXY_GROUP_NAME: &str = "PROXY";
+const TEST_DIR: &str = "subs/test";
+const SOURCE_STATS_PATH: &str = "subs/source_stats.json";
+const REPORT_PATH: &str = "subs/report.json";
+const NAMES_PATH: &str = "subs/names.json";
+const STICKY_PATH: &str = "subs/sticky.json";
+const RELEASE_DIR: &str = "subs/release";
+const RELEASE_TEMPLATE_PATH: &str = "conf/clash_release.yaml";
+// 没有可用节点时的退出码
+const EXIT_NO_NODES: u8 = 2;
+// 可用节点数不满足安全阈值，保留上次发布结果时的退出码
+const EXIT_SAFETY_ABORT: u8 = 3;
+// 收到中断信号时的退出码，与 shell 对 SIGINT 的约定一致
+const EXIT_INTERRUPTED: u8 = 130;
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     tracing::subscriber::set_global_default(
         FmtSubscriber::builder()
             .with_max_level(Level::INFO)
@@ -51,36 +128,105 @@ async fn main() {
         Ok(config) => {
             // 创建订阅测试所用的目录结构
             create_folder();
-            if args.server {
+            if let Some(Command::Release { action }) = args.command {
+                release_command(action)
+            } else if args.server {
                 // 服务端
                 // server::start_server(config).await
+                ExitCode::SUCCESS
             } else {
-                // 本地生成
-                run(config).await
+                // 本地生成，收到中断信号时丢弃运行中的任务，内核随 ClashMeta 一起被结束
+                tokio::select! {
+                    code = run(config, args.force) => code,
+                    signal = shutdown_signal() => {
+                        warn!("收到 {} 信号，正在停止内核并退出", signal);
+                        ExitCode::from(EXIT_INTERRUPTED)
+                    }
+                }
             }
         }
         Err(e) => {
-            error!("配置文件读取失败: {}", e)
+            error!("配置文件读取失败: {}", e);
+            ExitCode::FAILURE
         }
     }
 }
 
-async fn run(config: Settings) {
+#[cfg(unix)]
+async fn shutdown_signal() -> &'static str {
+    use tokio::signal::unix::signal;
+    use tokio::signal::unix::SignalKind;
+
+    let mut terminate = signal(SignalKind::terminate()).expect("注册 SIGTERM 处理失败");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => "SIGINT",
+        _ = terminate.recv() => "SIGTERM",
+    }
+}
+
+#[cfg(not(unix))]
+async fn shutdown_signal() -> &'static str {
+    let _ = tokio::signal::ctrl_c().await;
+    "Ctrl-C"
+}
+
+async fn run(config: Settings, force: bool) -> ExitCode {
     let test_yaml_path = "subs/test/config.yaml";
     let test_all_yaml_path = "subs/test/all.yaml";
     let release_yaml_path = env::current_dir().unwrap().join("clash.yaml");
     // let release_base64_path = env::current_dir().unwrap().join("proxies.txt");
     let test_clash_template_path = "conf/clash_test.yaml";
-    let release_clash_template_path = "conf/clash_release.yaml";
+    let release_clash_template_path = RELEASE_TEMPLATE_PATH;
+    let mut report = RunReport::new();
+    let name_template = match NameTemplate::parse(&with_capability_tags(&config.rename_pattern)) {
+        Ok(template) => template,
+        Err(e) => {
+            error!("重命名模板 rename_pattern 配置有误, {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
     let mut urls = config.subs;
     if config.need_add_pool {
         urls.extend(config.pools)
     }
-    let test_proxies = SubManager::get_proxies_from_urls(&urls).await;
+    // 目录和 glob 展开到具体文件，便于按文件统计和跳过失效来源
+    let mut source_stats = SourceStats::load(SOURCE_STATS_PATH);
+    let urls = urls
+        .iter()
+        .flat_map(|url| SubManager::expand_local_sources(url).unwrap_or_else(|| vec![url.clone()]))
+        .filter(|url| {
+            let skip = config.source_stats.auto_disable && source_stats.is_dead(url);
+            if skip {
+                info!(
+                    "「{}」已被标记为失效来源，跳过，删除 {} 中的记录可重新启用",
+                    url, SOURCE_STATS_PATH
+                );
+            }
+            !skip
+        })
+        .collect::<Vec<String>>();
+    // 解析时已移除流量、到期时间等信息节点，信息记录到来源统计中
+    let parsed =
+        SubManager::get_proxies_from_urls(&urls, config.dedup_policy, &config.ad_phrases).await;
+    for (source, info) in parsed.infos {
+        source_stats.set_info(&source, info);
+    }
+    let mut test_proxies = parsed.proxies;
+    if !config.transforms.is_empty() {
+        match transform::apply_transforms(test_proxies, &config.transforms) {
+            Ok(proxies) => test_proxies = proxies,
+            Err(e) => {
+                error!("节点过滤规则 transforms 配置有误, {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        SubManager::rename_dup_proxies_name(&mut test_proxies);
+    }
     info!("待测速节点个数：{}", &test_proxies.len());
+    report.tested = test_proxies.len();
     if test_proxies.is_empty() {
         error!("当前无可用的待测试订阅连接，请修改配置文件添加订阅链接或确保当前网络通顺");
-        return;
+        return ExitCode::from(EXIT_NO_NODES);
     }
 
     // 全部保存一下节点信息
@@ -104,171 +250,218 @@ async fn run(config: Settings) {
         );
     }
 
-    // 启动 Clash 内核
-    let external_port = 9095;
-    let mixed_port = 7998;
+    // 多个内核并行测试，每个内核依次领取未测试的分组
+    let parallelism = config.test_parallelism.clamp(1, group_size.max(1));
+    if parallelism > 1 {
+        info!("同时启动 {} 个内核并行测试", parallelism);
+    }
+    let group_results = test_connectivity(
+        &proxies_group,
+        parallelism,
+        |worker| new_mihomo_backend(worker, test_clash_template_path),
+        &config.connect_test,
+    )
+    .await;
+
     let mut useful_proxies = Vec::new();
-    for (index, proxies) in proxies_group.iter().enumerate() {
-        if group_size > 1 {
-            info!("正在测试第 {} 组", index + 1)
-        }
+    let mut node_delays: HashMap<String, i64> = HashMap::new();
+    for result in group_results {
+        useful_proxies.extend(result.useful_proxies);
+        node_delays.extend(result.delays);
+        report.failures.extend(result.failures);
+    }
+    info!("useful_proxies len: {}", useful_proxies.len());
+    // 只有连通性测试失败的节点可以在宽限期内保留，之后主动过滤掉的节点不保留
+    let useful_fingerprints: HashSet<String> =
+        useful_proxies.iter().map(SubManager::fingerprint).collect();
+    let connect_failed: HashSet<String> = test_proxies
+        .iter()
+        .map(SubManager::fingerprint)
+        .filter(|fingerprint| !useful_fingerprints.contains(fingerprint))
+        .collect();
 
-        SubManager::save_proxies_into_clash_file(
-            proxies,
-            test_clash_template_path.to_string(),
-            test_yaml_path.to_string(),
+    let source_yields = stats::collect_yields(&urls, &test_proxies, &useful_proxies);
+    for source in source_stats.update(&source_yields, &config.source_stats) {
+        warn!(
+            "「{}」连续 {} 次运行无可用节点",
+            source, config.source_stats.dead_threshold
         );
-
-        let mut clash_meta = ClashMeta::new(external_port, mixed_port);
-        if let Err(e) = clash_meta.start().await {
-            error!("原神启动失败，第一次启动可能会下载 geo 相关的文件，重新启动即可，打开 logs/clash.log，查看具体错误原因，{}", e);
-            clash_meta.stop().unwrap();
-            continue;
-        }
-
-        match clash_meta.get_group(TEST_PROXY_GROUP_NAME).await {
-            Ok(nodes) => {
-                info!(
-                    "开始测试 subs/test/config.yaml 中节点的延迟速度，节点总数：{}",
-                    nodes.all.len()
-                )
-            }
-            Err(e) => {
-                error!("获取节点数失败，请检查 clash 日志文件和 subs/test/config.yaml 生成的节点是否正确, {}", e);
-                clash_meta.stop().unwrap();
-                continue;
-            }
-        }
-
-        info!("开始测试连通性");
-        let delay_results = test_node_with_delay_config(&clash_meta, &config.connect_test).await;
-        let nodes = get_all_tested_nodes(&delay_results);
-        info!("连通性测试结果：{} 个节点可用", nodes.len());
-        if !nodes.is_empty() {
-            let cur_useful_proxies = proxies
-                .iter()
-                .filter(|&proxy| nodes.contains(&proxy.get_name().to_string()))
-                .cloned()
-                .collect::<Vec<Proxy>>();
-            info!("cur_useful_proxies len: {}", &cur_useful_proxies.len());
-            useful_proxies.extend(cur_useful_proxies);
-            info!("useful_proxies len: {}", useful_proxies.len());
-        }
-        clash_meta.stop().unwrap();
     }
+    stats::report(&source_yields, &source_stats);
 
+    report.available = useful_proxies.len();
     if useful_proxies.is_empty() {
         error!("当前无可用节点，请尝试更换订阅节点或重试");
-        return;
+        save_report(&mut report);
+        return ExitCode::from(EXIT_NO_NODES);
     } else {
         info!("当前总可用节点个数：{}", &useful_proxies.len());
     }
     let timeout: Duration = Duration::from_millis(config.connect_test.timeout + 2000);
-    if config.fast_mode {
-        SubManager::save_proxies_into_clash_file(
-            &useful_proxies,
-            release_clash_template_path.to_string(),
-            release_yaml_path.to_string_lossy().to_string(),
-        );
-        info!("release 文件地址：{}", release_yaml_path.to_string_lossy());
+    // 名称随重命名规则变化，规则变化后不再沿用之前的名称
+    let name_rule = if config.fast_mode || !config.rename_node {
+        "original".to_string()
+    } else {
+        format!("rename:{}", config.rename_pattern)
+    };
+    // 记录本次测试的延迟和能力，用于发布快照和 diff
+    let mut node_metrics: HashMap<String, NodeMetrics> = useful_proxies
+        .iter()
+        .map(|p| {
+            let metrics = NodeMetrics {
+                delay: node_delays.get(p.get_name()).copied(),
+                tags: vec![],
+            };
+            (SubManager::fingerprint(p), metrics)
+        })
+        .collect();
+    let mut release_proxies = if config.fast_mode {
+        useful_proxies
     } else {
-        let mut clash_meta = ClashMeta::new(external_port, mixed_port);
+        let mut clash_meta = match ClashMeta::with_free_ports() {
+            Ok(clash_meta) => clash_meta,
+            Err(e) => {
+                error!("分配内核端口失败, {}", e);
+                return ExitCode::FAILURE;
+            }
+        };
         SubManager::save_proxies_into_clash_file(
             &useful_proxies,
             test_clash_template_path.to_string(),
             test_yaml_path.to_string(),
         );
 
+        let nodes = &mut useful_proxies
+            .iter()
+            .map(|p| p.get_name().to_string())
+            .collect::<Vec<String>>();
+        // 每个节点绑定独立的监听端口，检测时无需切换分组，可以并发进行
+        if config.rename_node {
+            if let Err(e) = clash_meta.set_node_listeners(nodes) {
+                error!("分配节点监听端口失败, {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
         if let Err(e) = clash_meta.start().await {
             error!("原神启动失败，第一次启动可能会下载 geo 相关的文件，重新启动即可，打开 logs/clash.log，查看具体错误原因，{}", e);
-            clash_meta.stop().unwrap();
-            return;
+            return ExitCode::FAILURE;
         }
         info!("当前节点个数为：{}", useful_proxies.len());
 
-        let nodes = &mut useful_proxies
-            .iter()
-            .map(|p| p.get_name().to_string())
-            .collect::<Vec<String>>();
         let mut node_rename_map: HashMap<String, String> = HashMap::new();
+        let mut exit_ips: HashMap<String, IpAddr> = HashMap::new();
+        let mut node_vars: HashMap<String, HashMap<&str, String>> = HashMap::new();
         if config.rename_node {
             if nodes.is_empty() {
                 error!("当前无可用节点，请尝试更换订阅节点或重试");
-                clash_meta.stop().unwrap();
-                return;
+                return ExitCode::from(EXIT_NO_NODES);
             }
-            let mut i = 0;
-            while i < nodes.len() {
-                let node = &nodes[i];
-                let ip_result = clash_meta
-                    .set_group_proxy(TEST_PROXY_GROUP_NAME, node)
-                    .await;
-                if ip_result.is_ok() {
-                    let ip_result = cgi_trace::get_ip(&clash_meta.proxy_url, timeout).await;
-                    if ip_result.is_ok() {
-                        let (proxy_ip, from) = ip_result.unwrap();
-                        info!("「{}」ip: {} from: {}", node, proxy_ip, from);
-                        let mut gemini_is_ok = false;
-                        match website::gemini_is_ok(&clash_meta.proxy_url, timeout).await {
-                            Ok(_) => {
-                                info!("「{}」 gemini is ok", node);
-                                gemini_is_ok = true;
+            info!("开始检测节点，并发数：{}", config.probe_concurrency.max(1));
+            let probes = stream::iter(nodes.iter())
+                .map(|node| {
+                    let proxy = useful_proxies
+                        .iter()
+                        .find(|p| p.get_name() == node)
+                        .unwrap();
+                    let proxy_url = clash_meta.node_proxy_url(node);
+                    let delay = node_delays.get(node).copied();
+                    async move {
+                        let probe = match proxy_url {
+                            Some(proxy_url) => probe_node(proxy, &proxy_url, delay, timeout).await,
+                            None => {
+                                error!("节点 {} 没有对应的监听端口", node);
+                                Err(NodeFailure::new(
+                                    FailureStage::Probe,
+                                    FailureCategory::Unknown,
+                                    "没有对应的监听端口",
+                                ))
                             }
-                            Err(err) => {
-                                error!("「{}」 gemini is not ok, {:#}", node, err)
-                            }
-                        }
+                        };
+                        (node.clone(), proxy, probe)
+                    }
+                })
+                .buffered(config.probe_concurrency.max(1))
+                .collect::<Vec<_>>()
+                .await;
+            if let Err(e) = clash_meta.check_alive() {
+                error!("{}，部分节点的检测结果可能不完整", e);
+            }
 
-                        let mut claude_is_ok = false;
-                        match website::claude_is_ok(&clash_meta.proxy_url, timeout).await {
-                            Ok(_) => {
-                                info!("「{}」 claude is ok", node);
-                                claude_is_ok = true;
-                            }
-                            Err(err) => {
-                                error!("「{}」 claude is not ok, {:#}", node, err)
-                            }
-                        }
-                        if !gemini_is_ok && !claude_is_ok {
-                            nodes.remove(i);
-                            continue;
-                        }
-                        let ip_detail_result =
-                            ip::get_ip_detail(&proxy_ip, &clash_meta.proxy_url).await;
-                        let mut new_name = proxy_ip.to_string();
-                        match ip_detail_result {
-                            Ok(ip_detail) => {
-                                info!("{:?}", ip_detail);
-                                if config.rename_node {
-                                    new_name = config
-                                        .rename_pattern
-                                        .replace("${IP}", &proxy_ip.to_string())
-                                        .replace("${COUNTRYCODE}", &ip_detail.country_code)
-                                        .replace("${ISP}", &ip_detail.isp)
-                                        .replace("${CITY}", &ip_detail.city);
-                                }
-                            }
-                            Err(e) => {
-                                error!("获取节点 {node} 的 IP 信息失败, {e}");
+            let mut probed = Vec::new();
+            let mut probe_failures = BTreeMap::new();
+            for (node, proxy, probe) in probes {
+                let (proxy_ip, vars) = match probe {
+                    Ok(result) => result,
+                    Err(failure) => {
+                        probe_failures.insert(node, failure);
+                        continue;
+                    }
+                };
+                if let Some(metrics) = node_metrics.get_mut(&SubManager::fingerprint(proxy)) {
+                    metrics.tags = vars["TAGS"]
+                        .split('_')
+                        .filter(|t| !t.is_empty())
+                        .map(str::to_string)
+                        .collect();
+                }
+                node_vars.insert(node.clone(), vars);
+                exit_ips.insert(node.clone(), proxy_ip);
+                probed.push(node);
+            }
+            *nodes = probed;
+            let log = fs::read_to_string(clash_meta.log_path()).unwrap_or_default();
+            report.failures.extend(diagnosis::with_log_reasons(
+                &log,
+                probe_failures,
+                FailureStage::Probe,
+            ));
+
+            // 测速会占满带宽，逐个节点进行以免互相影响
+            if config.speed_test.enabled {
+                for node in nodes.iter() {
+                    let Some(proxy_url) = clash_meta.node_proxy_url(node) else {
+                        continue;
+                    };
+                    match speedtest::test_download(
+                        &config.speed_test.url,
+                        Duration::from_millis(config.speed_test.timeout as u64),
+                        Some(&proxy_url),
+                    )
+                    .await
+                    {
+                        Ok((_, bandwidth, _)) => {
+                            if let Some(vars) = node_vars.get_mut(node) {
+                                vars.insert("SPEED", rename::format_speed(bandwidth));
                             }
                         }
-                        if gemini_is_ok {
-                            new_name += "_Gemini";
-                        }
-                        if claude_is_ok {
-                            new_name += "_Claude";
+                        Err(e) => {
+                            error!("「{}」 测速失败, {}", node, e);
                         }
-                        node_rename_map.insert(node.clone(), new_name);
-                    } else {
-                        let err_msg = ip_result.err().unwrap();
-                        error!("获取节点 {} 的 IP 失败, {}", node, err_msg);
-                        nodes.remove(i);
                     }
-                } else {
-                    let err_msg = ip_result.err().unwrap();
-                    error!("设置节点 {} 失败, {}", node, err_msg);
                 }
-                i += 1;
+            }
+
+            // 不同入口的节点可能共用同一个出口，按出口 IP 只保留延迟最低的节点
+            let (kept, aliases) =
+                exit_ip::dedup_by_exit_ip(nodes, &exit_ips, &node_delays, config.max_per_exit_ip);
+            for alias in &aliases {
+                info!(
+                    "出口 IP {} 保留 {:?}，合并 {:?}",
+                    alias.ip, alias.kept, alias.collapsed
+                );
+            }
+            *nodes = kept;
+            report.exit_ip_aliases = aliases;
+
+            for (index, node) in nodes.iter().enumerate() {
+                if let Some(vars) = node_vars.get_mut(node) {
+                    vars.insert("INDEX", (index + 1).to_string());
+                    let mut new_name = name_template.render(vars).trim().to_string();
+                    if new_name.is_empty() {
+                        new_name = vars["IP"].clone();
+                    }
+                    node_rename_map.insert(node.clone(), new_name);
+                }
             }
         }
 
@@ -288,19 +481,246 @@ async fn run(config: Settings) {
             }
         }
 
-        SubManager::rename_dup_proxies_name(&mut release_proxies);
-        SubManager::save_proxies_into_clash_file(
-            &release_proxies,
-            release_clash_template_path.to_string(),
-            release_yaml_path.to_string_lossy().to_string(),
-        );
-        info!("release 文件地址：{}", release_yaml_path.to_string_lossy());
-        clash_meta.stop().unwrap();
+        // 内核可能已经提前退出，结束失败不影响发布
+        if let Err(e) = clash_meta.stop() {
+            warn!("内核停止失败, {}", e);
+        }
+        release_proxies
+    };
+
+    // diff 的基准是本次将被覆盖的 clash.yaml，restore 之后它不一定是最新的快照
+    let manifest = release::Manifest::load(RELEASE_DIR);
+    let previous = if release_yaml_path.exists() {
+        match release::current_snapshot(
+            Path::new(RELEASE_DIR),
+            &manifest,
+            &release_yaml_path,
+            manifest.latest(),
+        ) {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) => {
+                warn!("读取上次发布的 release 文件失败, {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let target = ReleaseTarget {
+        clash_yaml: &release_yaml_path,
+        template: release_clash_template_path,
+        sticky_path: STICKY_PATH,
+        names_path: NAMES_PATH,
+        name_rule: &name_rule,
+        force,
+    };
+    if let Err(reason) = publish_release(
+        &mut release_proxies,
+        &connect_failed,
+        &config.sticky,
+        &config.safety,
+        &target,
+        &mut report,
+    ) {
+        error!("{}，保留上次的发布结果，可使用 --force 强制发布", reason);
+        report.aborted = Some(reason);
+        save_report(&mut report);
+        return ExitCode::from(EXIT_SAFETY_ABORT);
+    }
+    info!("release 文件地址：{}", release_yaml_path.to_string_lossy());
+    // 没有可用节点或未通过安全检查时多半是本地网络异常，本次不计入来源统计
+    if let Err(e) = source_stats.save(SOURCE_STATS_PATH) {
+        error!("保存来源统计失败, {}", e);
     }
+    report.released = release_proxies.len();
+    let settings_hash = release::settings_hash(&["conf/config.toml", release_clash_template_path]);
+    match release::save_snapshot(
+        RELEASE_DIR,
+        &release_yaml_path,
+        &release_proxies,
+        &node_metrics,
+        &settings_hash,
+        &config.release,
+    ) {
+        Ok(snapshot) => {
+            info!("已保存发布快照 {}", snapshot.id);
+            let diff = release::diff(previous.as_ref(), &snapshot);
+            info!(
+                "相比上次发布新增 {} 个，移除 {} 个，重命名 {} 个节点",
+                diff.added.len(),
+                diff.removed.len(),
+                diff.renamed.len()
+            );
+            if let Err(e) = release::write_diff_report(&release_yaml_path, &diff) {
+                error!("保存发布 diff 失败, {}", e);
+            }
+        }
+        Err(e) => error!("保存发布快照失败, {}", e),
+    }
+    save_report(&mut report);
+    ExitCode::SUCCESS
 }
 
-#[allow(dead_code)]
-fn get_top_node(test_results: &Vec<HashMap<String, i64>>) -> (String, i64) {
+fn release_command(action: ReleaseAction) -> ExitCode {
+    let manifest = release::Manifest::load(RELEASE_DIR);
+    match action {
+        ReleaseAction::List => {
+            if manifest.snapshots.is_empty() {
+                info!("暂无发布快照");
+            }
+            for s in &manifest.snapshots {
+                info!(
+                    "{} 节点 {} 个，来源 {} 个，配置 {}",
+                    s.id,
+                    s.node_count,
+                    s.sources.len(),
+                    s.settings_hash
+                );
+            }
+        }
+        ReleaseAction::Diff { from, to } => {
+            let Some(old) = manifest.find(&from) else {
+                error!("快照 {} 不存在，可使用 release list 查看所有快照", from);
+                return ExitCode::FAILURE;
+            };
+            let new = match &to {
+                Some(to) => match manifest.find(to) {
+                    Some(snapshot) => snapshot.clone(),
+                    None => {
+                        error!("快照 {} 不存在，可使用 release list 查看所有快照", to);
+                        return ExitCode::FAILURE;
+                    }
+                },
+                // 未指定 TO 时与当前的 clash.yaml 比较，restore 之后它不一定是最新的快照
+                None => {
+                    let release_yaml_path = env::current_dir().unwrap().join("clash.yaml");
+                    match release::current_snapshot(
+                        Path::new(RELEASE_DIR),
+                        &manifest,
+                        &release_yaml_path,
+                        Some(old),
+                    ) {
+                        Ok(snapshot) => snapshot,
+                        Err(e) => {
+                            error!("读取 {} 失败, {}", release_yaml_path.to_string_lossy(), e);
+                            return ExitCode::FAILURE;
+                        }
+                    }
+                }
+            };
+            let diff = release::diff(Some(old), &new);
+            println!("{}", release::diff_markdown(&diff));
+        }
+        ReleaseAction::Restore { id } => {
+            if manifest.find(&id).is_none() {
+                error!("快照 {} 不存在，可使用 release list 查看所有快照", id);
+                return ExitCode::FAILURE;
+            }
+            let release_yaml_path = env::current_dir().unwrap().join("clash.yaml");
+            let snapshot = release::snapshot_path(Path::new(RELEASE_DIR), &id);
+            if let Err(e) = fs::copy(&snapshot, &release_yaml_path) {
+                error!("恢复快照 {} 失败, {}", id, e);
+                return ExitCode::FAILURE;
+            }
+            info!(
+                "已将快照 {} 恢复为 {}",
+                id,
+                release_yaml_path.to_string_lossy()
+            );
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+// 旧版本会在名称后自动追加 _Gemini、_Claude，模板中没有使用能力标签时保持这一行为
+fn with_capability_tags(pattern: &str) -> String {
+    match NameTemplate::parse(pattern) {
+        Ok(template) if !template.uses_any(&["TAGS", "GEMINI", "CLAUDE"]) => {
+            format!("{}$[_${{TAGS}}]", pattern)
+        }
+        _ => pattern.to_string(),
+    }
+}
+
+/// 发布 release 时读写的文件和选项，测试中文件指向临时目录
+struct ReleaseTarget<'a> {
+    clash_yaml: &'a Path,
+    template: &'a str,
+    // 节点状态和名称记录，release 文件写入后才保存
+    sticky_path: &'a str,
+    names_path: &'a str,
+    // 名称随重命名规则变化，规则变化后不再沿用之前的名称
+    name_rule: &'a str,
+    force: bool,
+}
+
+/// 加回宽限期内的降级节点并沿用之前的名称，通过安全检查后写入 release 文件
+/// 节点状态和名称记录只在 release 文件写入后保存，未通过安全检查时不修改任何文件并返回原因
+fn publish_release(
+    proxies: &mut Vec<Proxy>,
+    connect_failed: &HashSet<String>,
+    sticky: &StickyConfig,
+    safety: &SafetyConfig,
+    target: &ReleaseTarget,
+    report: &mut RunReport,
+) -> Result<(), String> {
+    // 之前发布过的节点偶尔测试失败时在宽限期内继续保留
+    let mut state = StickyState::load(target.sticky_path);
+    let degraded: HashSet<String> = state
+        .apply(proxies, connect_failed, sticky)
+        .iter()
+        .map(SubManager::fingerprint)
+        .collect();
+    if !degraded.is_empty() {
+        warn!("{} 个节点本次测试失败，在宽限期内继续保留", degraded.len());
+    }
+    // 沿用之前运行中同一节点的名称，保证客户端中选中的节点不会消失
+    let mut registry = NameRegistry::load(target.names_path, target.name_rule);
+    registry.assign(proxies);
+    // 降级节点在名称后加上后缀，便于在客户端中区分
+    for proxy in proxies
+        .iter_mut()
+        .filter(|p| degraded.contains(&SubManager::fingerprint(p)))
+    {
+        let name = format!("{}{}", proxy.get_name(), sticky.degraded_suffix);
+        proxy.set_name(&name);
+        report.degraded.push(name);
+    }
+
+    // 网络异常等情况下可用节点骤减，不覆盖上次的发布结果
+    // 检查的是加回降级节点后最终写入的节点列表
+    let previous = safety::count_released_proxies(target.clash_yaml);
+    if let Err(reason) = safety::check(safety, proxies.len(), previous) {
+        if !target.force {
+            return Err(reason);
+        }
+        warn!("{}，已指定 --force，继续发布", reason);
+    }
+
+    SubManager::save_proxies_into_clash_file(
+        proxies,
+        target.template.to_string(),
+        target.clash_yaml.to_string_lossy().to_string(),
+    );
+    if let Err(e) = state.save(target.sticky_path) {
+        error!("保存节点状态失败, {}", e);
+    }
+    if let Err(e) = registry.save(target.names_path) {
+        error!("保存节点名称记录失败, {}", e);
+    }
+    Ok(())
+}
+
+fn save_report(report: &mut RunReport) {
+    if let Err(e) = report.save(REPORT_PATH) {
+        error!("保存运行报告失败, {}", e);
+    }
+}
+
+/*
+获取每个节点多轮测试的平均延迟
+ */
+fn get_mean_delays(test_results: &Vec<HashMap<String, i64>>) -> HashMap<String, i64> {
     let mut combined_data: HashMap<String, Vec<i64>> = HashMap::new();
     for test in test_results {
         for (node, latency) in test {
@@ -310,24 +730,221 @@ fn get_top_node(test_results: &Vec<HashMap<String, i64>>) -> (String, i64) {
                 .push(*latency);
         }
     }
-    let node_stats: Vec<(String, i64)> = combined_data
-        .clone()
+    combined_data
         .into_iter()
         .map(|(node, latencies)| {
-            let sum: i64 = latencies.iter().sum();
-            let count = latencies.len() as i64;
-            let mean = sum / count;
+            let mean = latencies.iter().sum::<i64>() / latencies.len() as i64;
             (node, mean)
         })
-        .collect();
-    node_stats
+        .collect()
+}
+
+#[allow(dead_code)]
+fn get_top_node(test_results: &Vec<HashMap<String, i64>>) -> (String, i64) {
+    get_mean_delays(test_results)
         .into_iter()
         .min_by_key(|(_, mean)| *mean)
         .unwrap()
 }
 
-async fn test_node_with_delay_config(
-    clash_meta: &ClashMeta,
+/// 通过节点的独立监听端口查询出口 IP、AI 服务可用性和 IP 信息，失败时返回原因
+async fn probe_node(
+    proxy: &Proxy,
+    proxy_url: &str,
+    delay: Option<i64>,
+    timeout: Duration,
+) -> Result<(IpAddr, HashMap<&'static str, String>), NodeFailure> {
+    let node = proxy.get_name();
+    let (proxy_ip, from) = match cgi_trace::get_ip(proxy_url, timeout).await {
+        Ok(result) => result,
+        Err(e) => {
+            error!("获取节点 {} 的 IP 失败, {}", node, e);
+            let message = format!("获取出口 IP 失败, {}", e);
+            return Err(NodeFailure::new(
+                FailureStage::Probe,
+                diagnosis::classify(&message),
+                &message,
+            ));
+        }
+    };
+    info!("「{}」ip: {} from: {}", node, proxy_ip, from);
+    let mut gemini_is_ok = false;
+    match website::gemini_is_ok(proxy_url, timeout).await {
+        Ok(_) => {
+            info!("「{}」 gemini is ok", node);
+            gemini_is_ok = true;
+        }
+        Err(err) => {
+            error!("「{}」 gemini is not ok, {:#}", node, err)
+        }
+    }
+
+    let mut claude_is_ok = false;
+    match website::claude_is_ok(proxy_url, timeout).await {
+        Ok(_) => {
+            info!("「{}」 claude is ok", node);
+            claude_is_ok = true;
+        }
+        Err(err) => {
+            error!("「{}」 claude is not ok, {:#}", node, err)
+        }
+    }
+    if !gemini_is_ok && !claude_is_ok {
+        return Err(NodeFailure::new(
+            FailureStage::Probe,
+            FailureCategory::Unlock,
+            "Gemini 和 Claude 均不可用",
+        ));
+    }
+    let mut vars = rename::node_vars(proxy, &proxy_ip, delay);
+    match ip::get_ip_detail(&proxy_ip, proxy_url).await {
+        Ok(ip_detail) => {
+            info!("{:?}", ip_detail);
+            rename::add_ip_detail_vars(&mut vars, &ip_detail);
+        }
+        Err(e) => {
+            error!("获取节点 {node} 的 IP 信息失败, {e}");
+            rename::add_inferred_region_vars(&mut vars, node);
+        }
+    }
+    rename::add_capability_vars(&mut vars, gemini_is_ok, claude_is_ok);
+    Ok((proxy_ip, vars))
+}
+
+// 第 0 个内核沿用 subs/test 和 logs/clash.log，其余内核使用独立的目录和日志
+fn worker_paths(worker: usize) -> (String, String) {
+    if worker == 0 {
+        (TEST_DIR.to_string(), "logs/clash.log".to_string())
+    } else {
+        (
+            format!("{}/worker-{}", TEST_DIR, worker),
+            format!("logs/clash-{}.log", worker),
+        )
+    }
+}
+
+/// 一组节点的连通性测试结果
+struct GroupResult {
+    useful_proxies: Vec<Proxy>,
+    // 可用节点的平均延迟
+    delays: HashMap<String, i64>,
+    // 不可用节点及根据内核日志归类的原因
+    failures: BTreeMap<String, NodeFailure>,
+}
+
+/// 创建第 worker 个并行测试使用的 mihomo 后端，每个内核使用独立的目录、端口和日志
+fn new_mihomo_backend(
+    worker: usize,
+    test_clash_template_path: &str,
+) -> Result<MihomoBackend, Box<dyn std::error::Error>> {
+    let (work_dir, log_path) = worker_paths(worker);
+    if let Err(e) = copy_geo_files(Path::new(TEST_DIR), Path::new(&work_dir)) {
+        warn!("复制 geo 数据到 {} 失败, {}", work_dir, e);
+    }
+    let clash_meta = ClashMeta::with_free_ports()
+        .map_err(|e| format!("分配内核端口失败, {}", e))?
+        .with_work_dir(&work_dir, &log_path);
+    Ok(MihomoBackend::new(clash_meta, test_clash_template_path))
+}
+
+/// 使用多个后端并行测试各组节点的连通性，每个后端依次领取未测试的分组，结果按分组顺序返回
+async fn test_connectivity<B, F>(
+    proxies_group: &[Vec<Proxy>],
+    parallelism: usize,
+    new_backend: F,
+    connect_test: &DelayTestConfig,
+) -> Vec<GroupResult>
+where
+    B: TestBackend,
+    F: Fn(usize) -> Result<B, Box<dyn std::error::Error>>,
+{
+    let group_size = proxies_group.len();
+    let queue = Mutex::new(proxies_group.iter().enumerate().collect::<VecDeque<_>>());
+    let worker_results = join_all((0..parallelism.max(1)).map(|worker| {
+        let queue = &queue;
+        let new_backend = &new_backend;
+        async move {
+            // 每个后端只启动一次，之后的分组通过热加载切换节点
+            let mut backend = match new_backend(worker) {
+                Ok(backend) => backend,
+                Err(e) => {
+                    error!("创建测试后端失败, {}", e);
+                    return Vec::new();
+                }
+            };
+            let mut results = Vec::new();
+            loop {
+                let next = queue.lock().unwrap().pop_front();
+                let Some((index, proxies)) = next else {
+                    break;
+                };
+                if group_size > 1 {
+                    info!("正在测试第 {} 组", index + 1)
+                }
+                if let Some(result) = test_proxies_group(&mut backend, proxies, connect_test).await
+                {
+                    results.push((index, result));
+                }
+            }
+            if let Err(e) = backend.stop() {
+                warn!("测试后端停止失败, {}", e);
+            }
+            results
+        }
+    }))
+    .await;
+
+    // 按分组顺序合并结果，保证输出稳定
+    let mut group_results = worker_results.into_iter().flatten().collect::<Vec<_>>();
+    group_results.sort_by_key(|(index, _)| *index);
+    group_results
+        .into_iter()
+        .map(|(_, result)| result)
+        .collect()
+}
+
+/// 在后端中测试一组节点的连通性
+async fn test_proxies_group<B: TestBackend>(
+    backend: &mut B,
+    proxies: &[Proxy],
+    connect_test: &DelayTestConfig,
+) -> Option<GroupResult> {
+    if let Err(e) = backend.load_proxies(proxies).await {
+        error!(
+            "原神启动失败，第一次启动可能会下载 geo 相关的文件，重新启动即可，{}",
+            e
+        );
+        return None;
+    }
+
+    info!("开始测试连通性，节点总数：{}", proxies.len());
+    let delay_results = test_node_with_delay_config(backend, connect_test).await;
+    let nodes = get_all_tested_nodes(&delay_results);
+    info!("连通性测试结果：{} 个节点可用", nodes.len());
+    let (useful_proxies, failed_proxies): (Vec<Proxy>, Vec<Proxy>) = proxies
+        .iter()
+        .cloned()
+        .partition(|proxy| nodes.contains(&proxy.get_name().to_string()));
+    let failures = failed_proxies
+        .iter()
+        .map(|proxy| {
+            let failure = NodeFailure::new(
+                FailureStage::Connect,
+                FailureCategory::NoDelay,
+                "连通性测试无延迟",
+            );
+            (proxy.get_name().to_string(), failure)
+        })
+        .collect();
+    Some(GroupResult {
+        useful_proxies,
+        delays: get_mean_delays(&delay_results),
+        failures: diagnosis::with_log_reasons(&backend.log(), failures, FailureStage::Connect),
+    })
+}
+
+async fn test_node_with_delay_config<B: TestBackend>(
+    backend: &mut B,
     delay_test_config: &DelayTestConfig,
 ) -> Vec<HashMap<String, i64>> {
     const ROUND: i32 = 5;
@@ -336,16 +953,12 @@ async fn test_node_with_delay_config(
 
     // 预热 2 轮，DNS lookup
     for _ in 0..2 {
-        let _ = clash_meta
-            .test_group(TEST_PROXY_GROUP_NAME, delay_test_config)
-            .await;
+        let _ = backend.delay_test(delay_test_config).await;
     }
 
     for n in 0..ROUND {
         info!("测试第 {} 轮", n + 1);
-        let result = clash_meta
-            .test_group(TEST_PROXY_GROUP_NAME, delay_test_config)
-            .await;
+        let result = backend.delay_test(delay_test_config).await;
 
         match result {
             Ok(delay) => {
@@ -353,7 +966,12 @@ async fn test_node_with_delay_config(
                 info!("有速度节点个数为：{}", delay.len())
             }
             Err(e) => {
-                info!("当前测试轮完全没有速度, {}", e)
+                info!("当前测试轮完全没有速度, {}", e);
+                // 内核中途退出时后续轮次不再有意义
+                if let Err(e) = backend.check_alive() {
+                    error!("{}", e);
+                    break;
+                }
             }
         }
     }
@@ -436,7 +1054,10 @@ fn create_folder() {
 
 #[cfg(test)]
 mod tests {
+    use proxrs::testing::proxy;
+
     use super::*;
+    use crate::mock_backend::MockBackend;
 
     #[test]
     fn test_get_stable_nodes() {
@@ -469,6 +1090,133 @@ mod tests {
         println!("{:?}", get_top_node(&test_data));
     }
 
+    #[tokio::test]
+    async fn test_connectivity_with_mock_backend() {
+        let groups = vec![
+            vec![proxy(1, "a"), proxy(2, "b")],
+            vec![proxy(3, "c"), proxy(4, "d")],
+            vec![proxy(5, "e")],
+        ];
+        let connect_test = DelayTestConfig {
+            url: "http://www.google.com/generate_204".to_string(),
+            expected: Some(204),
+            timeout: 1000,
+        };
+        let results = test_connectivity(
+            &groups,
+            2,
+            |_| {
+                Ok(MockBackend::new()
+                    .with_latencies("a", &[Some(100), Some(200)])
+                    .with_latencies("c", &[None, None, Some(300)])
+                    .with_latencies("e", &[Some(50)])
+                    .with_failure("b", "remote error: tls: handshake failure"))
+            },
+            &connect_test,
+        )
+        .await;
+
+        assert_eq!(results.len(), 3);
+        let names = |result: &GroupResult| {
+            result
+                .useful_proxies
+                .iter()
+                .map(|p| p.get_name().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&results[0]), ["a"]);
+        assert_eq!(names(&results[1]), ["c"]);
+        assert_eq!(names(&results[2]), ["e"]);
+        // 预热 2 轮不计入结果
+        assert_eq!(results[0].delays["a"], 200);
+        assert_eq!(results[1].delays["c"], 300);
+        assert_eq!(results[0].failures["b"].category, FailureCategory::Tls);
+        assert_eq!(results[1].failures["d"].category, FailureCategory::NoDelay);
+    }
+
+    #[tokio::test]
+    async fn test_connectivity_backend_exit() {
+        let groups = vec![vec![proxy(1, "a")]];
+        let connect_test = DelayTestConfig {
+            url: "http://www.google.com/generate_204".to_string(),
+            expected: Some(204),
+            timeout: 1000,
+        };
+        let results = test_connectivity(
+            &groups,
+            1,
+            |_| {
+                Ok(MockBackend::new()
+                    .with_latencies("a", &[Some(100)])
+                    .exit_after(3))
+            },
+            &connect_test,
+        )
+        .await;
+        // 后端退出前完成的一轮仍然有效
+        assert_eq!(results[0].delays["a"], 100);
+    }
+
+    #[test]
+    fn test_safety_abort_keeps_state() {
+        let dir = env::temp_dir().join(format!("clash-butler-publish-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let clash_yaml = dir.join("clash.yaml");
+        let sticky_path = dir.join("sticky.json").to_string_lossy().to_string();
+        let names_path = dir.join("names.json").to_string_lossy().to_string();
+        let target = ReleaseTarget {
+            clash_yaml: &clash_yaml,
+            template: RELEASE_TEMPLATE_PATH,
+            sticky_path: &sticky_path,
+            names_path: &names_path,
+            name_rule: "original",
+            force: false,
+        };
+        let sticky = StickyConfig::default();
+        let safety = SafetyConfig {
+            min_nodes: 1,
+            max_drop: 0.5,
+        };
+        let mut report = RunReport::new();
+        let mut proxies = vec![proxy(1, "a"), proxy(2, "b"), proxy(3, "c")];
+        publish_release(
+            &mut proxies,
+            &HashSet::new(),
+            &sticky,
+            &safety,
+            &target,
+            &mut report,
+        )
+        .unwrap();
+        let read = || {
+            [&clash_yaml, Path::new(&sticky_path), Path::new(&names_path)]
+                .map(|path| fs::read_to_string(path).unwrap())
+        };
+        let published = read();
+
+        // 网络异常时只剩一个节点，安全检查不通过，节点状态和名称记录都不变
+        let mut proxies = vec![proxy(1, "a")];
+        let failed = [proxy(2, "b"), proxy(3, "c")]
+            .iter()
+            .map(SubManager::fingerprint)
+            .collect();
+        let result = publish_release(
+            &mut proxies,
+            &failed,
+            &StickyConfig {
+                grace_runs: 0,
+                ..Default::default()
+            },
+            &safety,
+            &target,
+            &mut report,
+        );
+        assert!(result.is_err());
+        assert_eq!(read(), published);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn test_rename_pattern() {
         let count = "${COUNTRYCODE}_${CITY}_${ISP}".matches('_').count();
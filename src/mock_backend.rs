@@ -0,0 +1,207 @@
+//! 测试用的进程内节点测试后端，按脚本返回每轮的延迟
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::io;
+
+use proxrs::protocol::Proxy;
+
+use crate::backend::TestBackend;
+use crate::clash::DelayTestConfig;
+
+/// 进程内的模拟后端，按脚本返回每轮的延迟，用于离线测试整个流程
+#[derive(Debug, Default)]
+pub struct MockBackend {
+    // 节点每轮测试的延迟，None 表示该轮超时，轮数超出脚本时重复最后一轮
+    latencies: HashMap<String, Vec<Option<i64>>>,
+    // 节点的失败原因，以 mihomo 日志格式写入 log
+    failures: HashMap<String, String>,
+    // 完成多少次延迟测试后模拟后端退出
+    exit_after: Option<usize>,
+    loaded: Vec<String>,
+    rounds: HashMap<String, usize>,
+    selected: Option<String>,
+    running: bool,
+    exited: bool,
+    delay_tests: usize,
+    pub starts: usize,
+    pub loads: usize,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        MockBackend::default()
+    }
+
+    pub fn with_latencies(mut self, node: &str, latencies: &[Option<i64>]) -> Self {
+        self.latencies.insert(node.to_string(), latencies.to_vec());
+        self
+    }
+
+    pub fn with_failure(mut self, node: &str, message: &str) -> Self {
+        self.failures.insert(node.to_string(), message.to_string());
+        self
+    }
+
+    pub fn exit_after(mut self, delay_tests: usize) -> Self {
+        self.exit_after = Some(delay_tests);
+        self
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.selected.as_deref()
+    }
+
+    fn latency(&mut self, node: &str) -> Option<i64> {
+        let round = self.rounds.entry(node.to_string()).or_default();
+        let script = self.latencies.get(node)?;
+        let latency = script.get(*round).or(script.last()).copied().flatten();
+        *round += 1;
+        latency
+    }
+}
+
+impl TestBackend for MockBackend {
+    async fn start(&mut self) -> Result<(), Box<dyn Error>> {
+        self.running = true;
+        self.exited = false;
+        self.starts += 1;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> io::Result<()> {
+        self.running = false;
+        Ok(())
+    }
+
+    async fn load_proxies(&mut self, proxies: &[Proxy]) -> Result<(), Box<dyn Error>> {
+        if !self.running {
+            self.start().await?;
+        }
+        self.loaded = proxies
+            .iter()
+            .map(|proxy| proxy.get_name().to_string())
+            .collect();
+        self.loads += 1;
+        Ok(())
+    }
+
+    async fn delay_test(
+        &mut self,
+        _config: &DelayTestConfig,
+    ) -> Result<HashMap<String, i64>, Box<dyn Error>> {
+        if !self.running {
+            return Err("后端未运行".into());
+        }
+        self.delay_tests += 1;
+        if self.exit_after.is_some_and(|n| self.delay_tests > n) {
+            self.running = false;
+            self.exited = true;
+            return Err("后端已退出".into());
+        }
+        let result = self
+            .loaded
+            .clone()
+            .into_iter()
+            .filter_map(|node| self.latency(&node).map(|latency| (node, latency)))
+            .collect::<HashMap<String, i64>>();
+        if result.is_empty() {
+            return Err("所有节点无速度".into());
+        }
+        Ok(result)
+    }
+
+    async fn select(&mut self, node: &str) -> Result<(), Box<dyn Error>> {
+        if !self.loaded.iter().any(|loaded| loaded == node) {
+            return Err(format!("节点 {} 不存在", node).into());
+        }
+        self.selected = Some(node.to_string());
+        Ok(())
+    }
+
+    fn proxy_url(&self) -> String {
+        "http://127.0.0.1:0".to_string()
+    }
+
+    fn node_proxy_url(&self, _node: &str) -> Option<String> {
+        None
+    }
+
+    fn check_alive(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.exited {
+            return Err("后端已退出".into());
+        }
+        Ok(())
+    }
+
+    fn log(&self) -> String {
+        let mut nodes = self.failures.keys().collect::<Vec<_>>();
+        nodes.sort();
+        nodes
+            .into_iter()
+            .map(|node| {
+                format!(
+                    "time=\"2025-01-01T00:00:00Z\" level=warning msg=\"[TCP] dial {} --> www.google.com:80 error: {}\"\n",
+                    node, self.failures[node]
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proxrs::testing::proxy;
+
+    use crate::backend::TestBackend;
+    use crate::clash::DelayTestConfig;
+    use crate::mock_backend::MockBackend;
+
+    fn config() -> DelayTestConfig {
+        DelayTestConfig {
+            url: "http://www.google.com/generate_204".to_string(),
+            expected: Some(204),
+            timeout: 1000,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_scripted_latencies() {
+        let mut backend = MockBackend::new()
+            .with_latencies("a", &[Some(100), None, Some(120)])
+            .with_latencies("b", &[None]);
+        backend
+            .load_proxies(&[proxy(1, "a"), proxy(2, "b")])
+            .await
+            .unwrap();
+        assert_eq!(backend.starts, 1);
+
+        let first = backend.delay_test(&config()).await.unwrap();
+        assert_eq!(first.get("a"), Some(&100));
+        assert!(!first.contains_key("b"));
+        assert!(backend.delay_test(&config()).await.is_err());
+        assert_eq!(backend.delay_test(&config()).await.unwrap()["a"], 120);
+        // 超出脚本后重复最后一轮
+        assert_eq!(backend.delay_test(&config()).await.unwrap()["a"], 120);
+
+        backend.select("a").await.unwrap();
+        assert_eq!(backend.selected(), Some("a"));
+        assert!(backend.select("c").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mock_exit() {
+        let mut backend = MockBackend::new()
+            .with_latencies("a", &[Some(100)])
+            .exit_after(1);
+        backend.load_proxies(&[proxy(1, "a")]).await.unwrap();
+        assert!(backend.delay_test(&config()).await.is_ok());
+        assert!(backend.delay_test(&config()).await.is_err());
+        assert!(backend.check_alive().is_err());
+
+        // 再次加载节点时重新启动
+        backend.load_proxies(&[proxy(1, "a")]).await.unwrap();
+        assert_eq!(backend.starts, 2);
+        assert!(backend.check_alive().is_ok());
+    }
+}
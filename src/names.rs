@@ -0,0 +1,204 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+
+use proxrs::protocol::Proxy;
+use proxrs::sub::SubManager;
+use regex::Regex;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::info;
+
+use crate::store::load_json;
+use crate::store::now;
+use crate::store::save_json;
+
+// 连续多少次运行未出现在 release 中的节点释放名称，释放前名称不会分配给其它节点
+const NAME_RETENTION_RUNS: u32 = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NameRecord {
+    pub name: String,
+    pub last_seen: u64,
+    // 连续未出现在 release 中的运行次数
+    #[serde(default)]
+    pub missed_runs: u32,
+}
+
+/// 节点指纹到名称的映射，跨多次运行保持同一节点的名称不变，保存在 subs/names.json
+/// 重命名规则变化时记录失效，所有节点重新命名
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct NameRegistry {
+    pub rule: String,
+    pub nodes: BTreeMap<String, NameRecord>,
+}
+
+impl NameRegistry {
+    pub fn load<P: AsRef<Path>>(path: P, rule: &str) -> Self {
+        let registry: NameRegistry = load_json(path);
+        if registry.rule != rule {
+            if !registry.nodes.is_empty() {
+                info!("重命名规则已变化，所有节点将重新命名");
+            }
+            return NameRegistry {
+                rule: rule.to_string(),
+                nodes: BTreeMap::new(),
+            };
+        }
+        registry
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        save_json(path, self)
+    }
+
+    /// 为节点分配名称：此前发布过的节点沿用原名称，新节点使用当前名称，重名时在末尾加序号
+    /// 替代 SubManager::rename_dup_proxies_name，已有节点的序号不会因排序变化而改变
+    /// 暂时消失的节点在释放前仍占用原名称，避免新节点以旧名称出现在订阅中
+    pub fn assign(&mut self, proxies: &mut [Proxy]) {
+        let now = now();
+        let fingerprints: Vec<String> = proxies.iter().map(SubManager::fingerprint).collect();
+
+        // 本次未出现的节点保留的名称
+        let current: HashSet<&String> = fingerprints.iter().collect();
+        let mut used: HashSet<String> = self
+            .nodes
+            .iter()
+            .filter(|(fingerprint, _)| !current.contains(fingerprint))
+            .map(|(_, record)| record.name.clone())
+            .collect();
+        let mut pending = Vec::new();
+        for (index, fingerprint) in fingerprints.iter().enumerate() {
+            match self.nodes.get(fingerprint) {
+                Some(record) if !used.contains(&record.name) => {
+                    used.insert(record.name.clone());
+                    proxies[index].set_name(&record.name);
+                }
+                _ => pending.push(index),
+            }
+        }
+
+        // 新节点沿用 rename_dup_proxies_name 的规则，去掉原有数字后缀后按需编号
+        let number_suffix = Regex::new(r"\d+$").unwrap();
+        let bases: Vec<String> = pending
+            .iter()
+            .map(|&i| number_suffix.replace(proxies[i].get_name(), "").to_string())
+            .collect();
+        let used_bases: HashSet<String> = used
+            .iter()
+            .map(|name| number_suffix.replace(name, "").to_string())
+            .collect();
+        let mut base_counts: HashMap<&str, usize> = HashMap::new();
+        for base in &bases {
+            *base_counts.entry(base).or_default() += 1;
+        }
+        for (&index, base) in pending.iter().zip(&bases) {
+            let mut name = base.clone();
+            if base_counts[base.as_str()] > 1 || used_bases.contains(base) {
+                let mut counter = 1;
+                name = format!("{}{}", base, counter);
+                while used.contains(&name) {
+                    counter += 1;
+                    name = format!("{}{}", base, counter);
+                }
+            }
+            used.insert(name.clone());
+            proxies[index].set_name(&name);
+        }
+
+        for record in self.nodes.values_mut() {
+            record.missed_runs += 1;
+        }
+        for (proxy, fingerprint) in proxies.iter().zip(fingerprints) {
+            self.nodes.insert(
+                fingerprint,
+                NameRecord {
+                    name: proxy.get_name().to_string(),
+                    last_seen: now,
+                    missed_runs: 0,
+                },
+            );
+        }
+        self.nodes
+            .retain(|_, record| record.missed_runs < NAME_RETENTION_RUNS);
+        proxies.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use proxrs::testing::proxy;
+
+    use super::*;
+
+    fn names(proxies: &[Proxy]) -> Vec<&str> {
+        proxies.iter().map(|p| p.get_name()).collect()
+    }
+
+    #[test]
+    fn test_assign_keeps_names() {
+        let mut registry = NameRegistry::default();
+        let mut first = vec![proxy(1, "HK"), proxy(2, "HK"), proxy(3, "JP")];
+        registry.assign(&mut first);
+        assert_eq!(names(&first), vec!["HK1", "HK2", "JP"]);
+
+        // HK1 消失，新节点不会占用其它节点的序号，存活节点名称不变
+        let mut second = vec![
+            proxy(4, "HK"),
+            proxy(2, "HK"),
+            proxy(3, "US"),
+            proxy(5, "JP"),
+        ];
+        registry.assign(&mut second);
+        assert_eq!(names(&second), vec!["HK2", "HK3", "JP", "JP1"]);
+        assert_eq!(second[1].get_port(), 4);
+        assert_eq!(second[2].get_port(), 3);
+    }
+
+    #[test]
+    fn test_assign_never_reuses_names() {
+        let mut registry = NameRegistry::default();
+        registry.assign(&mut [proxy(1, "HK")]);
+
+        // 原节点消失期间，同名的新节点不会拿到 HK
+        let mut second = vec![proxy(2, "HK")];
+        registry.assign(&mut second);
+        assert_eq!(names(&second), vec!["HK1"]);
+
+        // 原节点回来后仍然使用 HK
+        let mut third = vec![proxy(1, "HK"), proxy(2, "HK")];
+        registry.assign(&mut third);
+        assert_eq!(names(&third), vec!["HK", "HK1"]);
+        assert_eq!(third[0].get_port(), 1);
+    }
+
+    #[test]
+    fn test_assign_expires_names() {
+        let mut registry = NameRegistry::default();
+        registry.assign(&mut [proxy(1, "HK")]);
+        for _ in 1..NAME_RETENTION_RUNS {
+            registry.assign(&mut [proxy(2, "JP")]);
+        }
+        assert!(registry.nodes.values().any(|r| r.name == "HK"));
+
+        registry.assign(&mut [proxy(2, "JP")]);
+        assert!(registry.nodes.values().all(|r| r.name != "HK"));
+        let mut proxies = vec![proxy(3, "HK")];
+        registry.assign(&mut proxies);
+        assert_eq!(names(&proxies), vec!["HK"]);
+    }
+
+    #[test]
+    fn test_rule_change_resets() {
+        let path = std::env::temp_dir().join("clash-butler-names-test.json");
+        let mut registry = NameRegistry::load(&path, "a");
+        registry.assign(&mut [proxy(1, "HK")]);
+        registry.save(&path).unwrap();
+        assert_eq!(NameRegistry::load(&path, "a").nodes.len(), 1);
+        assert!(NameRegistry::load(&path, "b").nodes.is_empty());
+        fs::remove_file(path).unwrap();
+    }
+}
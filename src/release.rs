@@ -0,0 +1,544 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use chrono::Local;
+use proxrs::protocol::Proxy;
+use proxrs::sub::SubManager;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+use tracing::info;
+
+use crate::rename::source_label;
+use crate::store::load_json;
+use crate::store::save_json;
+
+const MANIFEST_FILE: &str = "manifest.json";
+// 延迟变化超过该值时记入 diff
+const DELAY_CHANGE_MS: i64 = 100;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReleaseConfig {
+    // 最多保留的历史快照个数，0 表示不限制
+    pub keep: usize,
+    // 快照最多保留的天数，0 表示不限制
+    pub max_age_days: u64,
+}
+
+impl Default for ReleaseConfig {
+    fn default() -> Self {
+        ReleaseConfig {
+            keep: 30,
+            max_age_days: 0,
+        }
+    }
+}
+
+/// 单个节点在快照中的信息
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotNode {
+    pub name: String,
+    // 平均延迟，宽限期内保留的节点没有本次测试结果
+    #[serde(default)]
+    pub delay: Option<i64>,
+    // Gemini、Claude 等可用能力
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// 本次测试得到的节点指标，按节点指纹记录
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeMetrics {
+    pub delay: Option<i64>,
+    pub tags: Vec<String>,
+}
+
+/// 一次发布的快照信息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub id: String,
+    pub created_at: i64,
+    pub node_count: usize,
+    pub sources: Vec<String>,
+    pub settings_hash: String,
+    // 节点指纹到节点信息
+    pub nodes: BTreeMap<String, SnapshotNode>,
+}
+
+/// subs/release/manifest.json，按发布时间先后记录所有快照
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub snapshots: Vec<Snapshot>,
+}
+
+/// 两个快照之间的节点变化，按节点指纹比较
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct SnapshotDiff {
+    pub from: String,
+    pub to: String,
+    pub added: Vec<DiffNode>,
+    pub removed: Vec<DiffNode>,
+    pub renamed: Vec<RenamedNode>,
+    pub changed: Vec<ChangedNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiffNode {
+    pub fingerprint: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RenamedNode {
+    pub fingerprint: String,
+    pub old_name: String,
+    pub new_name: String,
+}
+
+/// 延迟或能力发生变化的节点
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChangedNode {
+    pub fingerprint: String,
+    pub name: String,
+    pub old_delay: Option<i64>,
+    pub new_delay: Option<i64>,
+    pub old_tags: Vec<String>,
+    pub new_tags: Vec<String>,
+}
+
+impl Manifest {
+    pub fn load<P: AsRef<Path>>(dir: P) -> Self {
+        load_json(dir.as_ref().join(MANIFEST_FILE))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, dir: P) -> std::io::Result<()> {
+        save_json(dir.as_ref().join(MANIFEST_FILE), self)
+    }
+
+    pub fn find(&self, id: &str) -> Option<&Snapshot> {
+        self.snapshots.iter().find(|s| s.id == id)
+    }
+
+    pub fn latest(&self) -> Option<&Snapshot> {
+        self.snapshots.last()
+    }
+
+    /// 按保留策略删除过期的快照，返回被删除的快照 id
+    pub fn prune(&mut self, config: &ReleaseConfig, now: i64) -> Vec<String> {
+        let mut removed = Vec::new();
+        let max_age = config.max_age_days as i64 * 24 * 3600;
+        let len = self.snapshots.len();
+        let mut index = 0;
+        self.snapshots.retain(|s| {
+            let over_count = config.keep > 0 && index + config.keep < len;
+            let too_old = max_age > 0 && now - s.created_at > max_age;
+            index += 1;
+            if over_count || too_old {
+                removed.push(s.id.clone());
+                false
+            } else {
+                true
+            }
+        });
+        removed
+    }
+}
+
+/// 将本次发布的 release 文件保存为快照，并按保留策略清理旧快照
+pub fn save_snapshot<P: AsRef<Path>>(
+    dir: P,
+    release_path: &Path,
+    proxies: &[Proxy],
+    metrics: &HashMap<String, NodeMetrics>,
+    settings_hash: &str,
+    config: &ReleaseConfig,
+) -> std::io::Result<Snapshot> {
+    let dir = dir.as_ref();
+    let now = Local::now();
+    let mut manifest = Manifest::load(dir);
+    let mut id = now.format("%Y%m%d-%H%M%S").to_string();
+    // 同一秒内多次发布时加序号
+    let mut counter = 1;
+    while manifest.find(&id).is_some() {
+        id = format!("{}-{}", now.format("%Y%m%d-%H%M%S"), counter);
+        counter += 1;
+    }
+    fs::copy(release_path, snapshot_path(dir, &id))?;
+
+    // manifest 与 clash.yaml 一起发布，只记录来源的域名或文件名，不暴露订阅地址中的 token
+    let sources: BTreeSet<String> = proxies
+        .iter()
+        .flat_map(|p| p.sources.iter().map(|s| source_label(s)))
+        .collect();
+    let snapshot = Snapshot {
+        id,
+        created_at: now.timestamp(),
+        node_count: proxies.len(),
+        sources: sources.into_iter().collect(),
+        settings_hash: settings_hash.to_string(),
+        nodes: proxies
+            .iter()
+            .map(|p| {
+                let fingerprint = SubManager::fingerprint(p);
+                let metrics = metrics.get(&fingerprint).cloned().unwrap_or_default();
+                let node = SnapshotNode {
+                    name: p.get_name().to_string(),
+                    delay: metrics.delay,
+                    tags: metrics.tags,
+                };
+                (fingerprint, node)
+            })
+            .collect(),
+    };
+    manifest.snapshots.push(snapshot.clone());
+    for id in manifest.prune(config, now.timestamp()) {
+        info!("删除过期的发布快照 {}", id);
+        let _ = fs::remove_file(snapshot_path(dir, &id));
+    }
+    manifest.save(dir)?;
+    Ok(snapshot)
+}
+
+pub fn snapshot_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}.yaml", id))
+}
+
+/// 当前 release 文件对应的快照
+/// 内容与某个快照文件一致时（如 restore 之后）直接使用该快照，否则根据文件中的节点生成，
+/// 节点的延迟和能力取自 base 中的同一节点
+pub fn current_snapshot(
+    dir: &Path,
+    manifest: &Manifest,
+    release_path: &Path,
+    base: Option<&Snapshot>,
+) -> Result<Snapshot, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(release_path)?;
+    let matched = manifest.snapshots.iter().rev().find(|s| {
+        fs::read_to_string(snapshot_path(dir, &s.id)).is_ok_and(|snapshot| snapshot == content)
+    });
+    if let Some(snapshot) = matched {
+        return Ok(snapshot.clone());
+    }
+
+    let proxies = SubManager::parse_content(content)?;
+    let empty = BTreeMap::new();
+    let base_nodes = base.map_or(&empty, |s| &s.nodes);
+    Ok(Snapshot {
+        id: release_path.to_string_lossy().to_string(),
+        created_at: Local::now().timestamp(),
+        node_count: proxies.len(),
+        sources: vec![],
+        settings_hash: String::new(),
+        nodes: proxies
+            .iter()
+            .map(|p| {
+                let fingerprint = SubManager::fingerprint(p);
+                let node = SnapshotNode {
+                    name: p.get_name().to_string(),
+                    ..base_nodes.get(&fingerprint).cloned().unwrap_or_default()
+                };
+                (fingerprint, node)
+            })
+            .collect(),
+    })
+}
+
+/// 比较两个快照中的节点，old 为 None 时所有节点都视为新增
+pub fn diff(old: Option<&Snapshot>, new: &Snapshot) -> SnapshotDiff {
+    let empty = BTreeMap::new();
+    let old_nodes = old.map_or(&empty, |s| &s.nodes);
+    let mut diff = SnapshotDiff {
+        from: old.map(|s| s.id.clone()).unwrap_or_default(),
+        to: new.id.clone(),
+        ..Default::default()
+    };
+    for (fingerprint, node) in &new.nodes {
+        let Some(old_node) = old_nodes.get(fingerprint) else {
+            diff.added.push(DiffNode {
+                fingerprint: fingerprint.clone(),
+                name: node.name.clone(),
+            });
+            continue;
+        };
+        if old_node.name != node.name {
+            diff.renamed.push(RenamedNode {
+                fingerprint: fingerprint.clone(),
+                old_name: old_node.name.clone(),
+                new_name: node.name.clone(),
+            });
+        }
+        let delay_changed = match (old_node.delay, node.delay) {
+            (Some(old), Some(new)) => (new - old).abs() >= DELAY_CHANGE_MS,
+            (old, new) => old.is_some() != new.is_some(),
+        };
+        if delay_changed || old_node.tags != node.tags {
+            diff.changed.push(ChangedNode {
+                fingerprint: fingerprint.clone(),
+                name: node.name.clone(),
+                old_delay: old_node.delay,
+                new_delay: node.delay,
+                old_tags: old_node.tags.clone(),
+                new_tags: node.tags.clone(),
+            });
+        }
+    }
+    for (fingerprint, node) in old_nodes {
+        if !new.nodes.contains_key(fingerprint) {
+            diff.removed.push(DiffNode {
+                fingerprint: fingerprint.clone(),
+                name: node.name.clone(),
+            });
+        }
+    }
+    diff
+}
+
+/// 生成便于审阅的 Markdown 格式 diff
+pub fn diff_markdown(diff: &SnapshotDiff) -> String {
+    let from = if diff.from.is_empty() {
+        "-"
+    } else {
+        &diff.from
+    };
+    let mut md = format!("# Release diff {} -> {}\n\n", from, diff.to);
+    let _ = writeln!(
+        md,
+        "新增 {}，移除 {}，重命名 {}，变化 {}\n",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.renamed.len(),
+        diff.changed.len()
+    );
+    if !diff.added.is_empty() {
+        md.push_str("## 新增\n\n");
+        for node in &diff.added {
+            let _ = writeln!(md, "- `{}` {}", node.fingerprint, node.name);
+        }
+        md.push('\n');
+    }
+    if !diff.removed.is_empty() {
+        md.push_str("## 移除\n\n");
+        for node in &diff.removed {
+            let _ = writeln!(md, "- `{}` {}", node.fingerprint, node.name);
+        }
+        md.push('\n');
+    }
+    if !diff.renamed.is_empty() {
+        md.push_str("## 重命名\n\n");
+        for node in &diff.renamed {
+            let _ = writeln!(
+                md,
+                "- `{}` {} -> {}",
+                node.fingerprint, node.old_name, node.new_name
+            );
+        }
+        md.push('\n');
+    }
+    if !diff.changed.is_empty() {
+        md.push_str("## 延迟和能力变化\n\n| 节点 | 延迟 | 能力 |\n| --- | --- | --- |\n");
+        let delay = |d: Option<i64>| d.map_or("-".to_string(), |d| format!("{}ms", d));
+        let tags = |t: &[String]| {
+            if t.is_empty() {
+                "-".to_string()
+            } else {
+                t.join(",")
+            }
+        };
+        for node in &diff.changed {
+            let _ = writeln!(
+                md,
+                "| {} | {} -> {} | {} -> {} |",
+                node.name,
+                delay(node.old_delay),
+                delay(node.new_delay),
+                tags(&node.old_tags),
+                tags(&node.new_tags)
+            );
+        }
+        md.push('\n');
+    }
+    md
+}
+
+/// 将 diff 以 JSON 和 Markdown 格式写到 release 文件旁，如 clash.diff.json 和 clash.diff.md
+pub fn write_diff_report(release_path: &Path, diff: &SnapshotDiff) -> std::io::Result<()> {
+    save_json(release_path.with_extension("diff.json"), diff)?;
+    fs::write(release_path.with_extension("diff.md"), diff_markdown(diff))
+}
+
+/// 计算配置文件和模板的哈希，用于判断两次发布的配置是否一致
+pub fn settings_hash(paths: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for path in paths {
+        hasher.update(fs::read(path).unwrap_or_default());
+    }
+    hasher.finalize()[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(id: &str, created_at: i64, nodes: &[(&str, &str)]) -> Snapshot {
+        Snapshot {
+            id: id.to_string(),
+            created_at,
+            node_count: nodes.len(),
+            sources: vec![],
+            settings_hash: String::new(),
+            nodes: nodes
+                .iter()
+                .map(|(fp, name)| {
+                    let node = SnapshotNode {
+                        name: name.to_string(),
+                        delay: Some(100),
+                        tags: vec![],
+                    };
+                    (fp.to_string(), node)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = snapshot("a", 0, &[("1", "HK1"), ("2", "HK2"), ("3", "JP")]);
+        let mut new = snapshot("b", 0, &[("1", "HK1"), ("2", "HK"), ("4", "US")]);
+        new.nodes.get_mut("1").unwrap().delay = Some(300);
+        new.nodes.get_mut("2").unwrap().tags = vec!["Gemini".to_string()];
+        let node = |fp: &str, name: &str| DiffNode {
+            fingerprint: fp.to_string(),
+            name: name.to_string(),
+        };
+
+        let diff = diff(Some(&old), &new);
+        assert_eq!(diff.added, vec![node("4", "US")]);
+        assert_eq!(diff.removed, vec![node("3", "JP")]);
+        assert_eq!(
+            diff.renamed,
+            vec![RenamedNode {
+                fingerprint: "2".to_string(),
+                old_name: "HK2".to_string(),
+                new_name: "HK".to_string(),
+            }]
+        );
+        assert_eq!(diff.changed.len(), 2);
+        assert_eq!(diff.changed[0].new_delay, Some(300));
+        assert_eq!(diff.changed[1].new_tags, vec!["Gemini".to_string()]);
+
+        let md = diff_markdown(&diff);
+        assert!(md.contains("# Release diff a -> b"));
+        assert!(md.contains("- `4` US"));
+        assert!(md.contains("| HK1 | 100ms -> 300ms | - -> - |"));
+    }
+
+    #[test]
+    fn test_diff_without_previous() {
+        let new = snapshot("b", 0, &[("1", "HK1")]);
+        let diff = diff(None, &new);
+        assert_eq!(diff.from, "");
+        assert_eq!(diff.added.len(), 1);
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut manifest = Manifest {
+            snapshots: vec![
+                snapshot("a", 0, &[]),
+                snapshot("b", 100, &[]),
+                snapshot("c", 200, &[]),
+                snapshot("d", 300, &[]),
+            ],
+        };
+        let config = ReleaseConfig {
+            keep: 3,
+            max_age_days: 0,
+        };
+        assert_eq!(manifest.prune(&config, 300), vec!["a".to_string()]);
+        let config = ReleaseConfig {
+            keep: 0,
+            max_age_days: 1,
+        };
+        assert_eq!(manifest.prune(&config, 86400 + 150), vec!["b".to_string()]);
+        assert_eq!(manifest.latest().unwrap().id, "d");
+    }
+
+    #[test]
+    fn test_save_snapshot() {
+        let dir = std::env::temp_dir().join(format!("clash-butler-release-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let release = dir.join("clash.yaml");
+        fs::write(&release, "proxies: []\n").unwrap();
+        let mut proxy = Proxy::from_link(
+            "ss://YWVzLTEyOC1nY206ZDljNTc3MzI4ZmIzNDlmZQ==@120.232.73.68:40676#HK".to_string(),
+        )
+        .unwrap();
+        proxy.add_source("https://sub.example.com/api/v1/client/subscribe?token=secret");
+        proxy.add_source("./subs/raw/free.yaml");
+        let config = ReleaseConfig {
+            keep: 1,
+            max_age_days: 0,
+        };
+
+        let metrics = HashMap::new();
+        let first =
+            save_snapshot(&dir, &release, &[proxy.clone()], &metrics, "h", &config).unwrap();
+        let second = save_snapshot(&dir, &release, &[proxy], &metrics, "h", &config).unwrap();
+        assert_ne!(first.id, second.id);
+        assert!(!snapshot_path(&dir, &first.id).exists());
+        assert!(snapshot_path(&dir, &second.id).exists());
+        let manifest = Manifest::load(&dir);
+        assert_eq!(manifest.snapshots.len(), 1);
+        assert_eq!(
+            manifest.snapshots[0].sources,
+            vec!["free".to_string(), "sub.example.com".to_string()]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_current_snapshot() {
+        let dir = std::env::temp_dir().join(format!("clash-butler-current-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let release = dir.join("clash.yaml");
+        let hk = "{name: HK, type: ss, server: 120.232.73.68, port: 40676, cipher: aes-128-gcm, password: d9c577328fb349fe}";
+        let jp = "{name: JP, type: ss, server: 120.232.73.68, port: 40677, cipher: aes-128-gcm, password: d9c577328fb349fe}";
+        let old_content = format!("proxies:\n  - {}\n", hk);
+        let new_content = format!("proxies:\n  - {}\n  - {}\n", hk, jp);
+        let hk_fingerprint =
+            SubManager::fingerprint(&SubManager::parse_content(old_content.clone()).unwrap()[0]);
+        let mut manifest = Manifest::default();
+        let old = snapshot("a", 0, &[(&hk_fingerprint, "HK")]);
+        manifest.snapshots.push(old.clone());
+        manifest.snapshots.push(snapshot("b", 1, &[("x", "X")]));
+        fs::write(snapshot_path(&dir, "a"), &old_content).unwrap();
+        fs::write(snapshot_path(&dir, "b"), &new_content).unwrap();
+
+        // restore 之后 release 文件与快照 a 一致
+        fs::write(&release, &old_content).unwrap();
+        let current = current_snapshot(&dir, &manifest, &release, Some(&old)).unwrap();
+        assert_eq!(current.id, "a");
+
+        // 手动修改后按文件内容生成，沿用 base 中的延迟
+        fs::write(&release, format!("proxies:\n  - {}\n  - {}\n", jp, hk)).unwrap();
+        let current = current_snapshot(&dir, &manifest, &release, Some(&old)).unwrap();
+        let diff = diff(Some(&old), &current);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].name, "JP");
+        assert!(diff.changed.is_empty());
+        assert_eq!(current.nodes[&hk_fingerprint].delay, Some(100));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
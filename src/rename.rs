@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use proxrs::protocol::Proxy;
+use proxrs::region;
+
+use crate::ip::IpDetail;
+
+/// rename_pattern 支持的变量
+pub const VARIABLES: &[&str] = &[
+    "NAME",
+    "IP",
+    "FLAG",
+    "COUNTRY",
+    "COUNTRYCODE",
+    "REGION",
+    "CITY",
+    "ISP",
+    "ASN",
+    "PROTOCOL",
+    "SOURCE",
+    "DELAY",
+    "SPEED",
+    "TAGS",
+    "GEMINI",
+    "CLAUDE",
+    "INDEX",
+];
+
+/// 节点重命名模板
+/// - `${VAR}` 引用变量，变量缺失时为空
+/// - `${VAR|upper|truncate:8|default:未知}` 按顺序应用过滤器，支持 upper、lower、truncate:N、pad:N、default:X
+/// - `$[...]` 条件片段，其中任一变量为空时整段不输出，如 `$[_${CITY}]`
+/// - `$$` 输出 `$` 本身
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameTemplate {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Var { name: String, filters: Vec<Filter> },
+    Section(Vec<Segment>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Upper,
+    Lower,
+    Truncate(usize),
+    Pad(usize),
+    Default(String),
+}
+
+impl NameTemplate {
+    pub fn parse(pattern: &str) -> Result<NameTemplate, Box<dyn std::error::Error>> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut pos = 0;
+        let segments = parse_segments(&chars, &mut pos, false)?;
+        Ok(NameTemplate { segments })
+    }
+
+    /// 模板中是否引用了任一给定变量
+    pub fn uses_any(&self, names: &[&str]) -> bool {
+        uses_any(&self.segments, names)
+    }
+
+    pub fn render(&self, vars: &HashMap<&str, String>) -> String {
+        render_segments(&self.segments, vars).0
+    }
+}
+
+fn parse_segments(
+    chars: &[char],
+    pos: &mut usize,
+    in_section: bool,
+) -> Result<Vec<Segment>, Box<dyn std::error::Error>> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    while *pos < chars.len() {
+        let c = chars[*pos];
+        let next = chars.get(*pos + 1).copied();
+        match (c, next) {
+            ('$', Some('$')) => {
+                text.push('$');
+                *pos += 2;
+            }
+            ('$', Some('{')) => {
+                flush_text(&mut text, &mut segments);
+                *pos += 2;
+                let start = *pos;
+                while *pos < chars.len() && chars[*pos] != '}' {
+                    *pos += 1;
+                }
+                if *pos >= chars.len() {
+                    return Err("rename_pattern 中的 ${ 缺少对应的 }".into());
+                }
+                let expr: String = chars[start..*pos].iter().collect();
+                *pos += 1;
+                segments.push(parse_var(&expr)?);
+            }
+            ('$', Some('[')) => {
+                flush_text(&mut text, &mut segments);
+                *pos += 2;
+                segments.push(Segment::Section(parse_segments(chars, pos, true)?));
+            }
+            (']', _) if in_section => {
+                flush_text(&mut text, &mut segments);
+                *pos += 1;
+                return Ok(segments);
+            }
+            _ => {
+                text.push(c);
+                *pos += 1;
+            }
+        }
+    }
+    if in_section {
+        return Err("rename_pattern 中的 $[ 缺少对应的 ]".into());
+    }
+    flush_text(&mut text, &mut segments);
+    Ok(segments)
+}
+
+fn flush_text(text: &mut String, segments: &mut Vec<Segment>) {
+    if !text.is_empty() {
+        segments.push(Segment::Text(std::mem::take(text)));
+    }
+}
+
+fn parse_var(expr: &str) -> Result<Segment, Box<dyn std::error::Error>> {
+    let mut parts = expr.split('|');
+    let name = parts.next().unwrap_or_default().trim().to_uppercase();
+    if !VARIABLES.contains(&name.as_str()) {
+        return Err(format!("rename_pattern 中存在未知变量: {}", name).into());
+    }
+    let filters = parts.map(parse_filter).collect::<Result<Vec<_>, _>>()?;
+    Ok(Segment::Var { name, filters })
+}
+
+fn parse_filter(filter: &str) -> Result<Filter, Box<dyn std::error::Error>> {
+    let (name, arg) = match filter.split_once(':') {
+        Some((name, arg)) => (name.trim(), Some(arg)),
+        None => (filter.trim(), None),
+    };
+    let filter = match (name, arg) {
+        ("upper", None) => Filter::Upper,
+        ("lower", None) => Filter::Lower,
+        ("truncate", Some(n)) => Filter::Truncate(n.trim().parse()?),
+        ("pad", Some(n)) => Filter::Pad(n.trim().parse()?),
+        ("default", Some(value)) => Filter::Default(value.to_string()),
+        _ => return Err(format!("rename_pattern 中存在未知过滤器: {}", filter).into()),
+    };
+    Ok(filter)
+}
+
+fn uses_any(segments: &[Segment], names: &[&str]) -> bool {
+    segments.iter().any(|segment| match segment {
+        Segment::Text(_) => false,
+        Segment::Var { name, .. } => names.contains(&name.as_str()),
+        Segment::Section(children) => uses_any(children, names),
+    })
+}
+
+/// 返回渲染结果以及其中的变量是否都有值
+fn render_segments(segments: &[Segment], vars: &HashMap<&str, String>) -> (String, bool) {
+    let mut result = String::new();
+    let mut complete = true;
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => result.push_str(text),
+            Segment::Var { name, filters } => {
+                let value = vars.get(name.as_str()).map_or("", |v| v.trim());
+                let value = filters.iter().fold(value.to_string(), |value, filter| {
+                    apply_filter(value, filter)
+                });
+                if value.is_empty() {
+                    complete = false;
+                }
+                result.push_str(&value);
+            }
+            Segment::Section(children) => {
+                let (section, section_complete) = render_segments(children, vars);
+                if section_complete {
+                    result.push_str(&section);
+                }
+            }
+        }
+    }
+    (result, complete)
+}
+
+fn apply_filter(value: String, filter: &Filter) -> String {
+    match filter {
+        Filter::Upper => value.to_uppercase(),
+        Filter::Lower => value.to_lowercase(),
+        Filter::Truncate(n) => value.chars().take(*n).collect(),
+        Filter::Pad(n) => format!("{:0>width$}", value, width = *n),
+        Filter::Default(default) if value.is_empty() => default.clone(),
+        Filter::Default(_) => value,
+    }
+}
+
+/// 节点自身和测速结果相关的变量
+pub fn node_vars(proxy: &Proxy, ip: &IpAddr, delay: Option<i64>) -> HashMap<&'static str, String> {
+    let protocol = serde_json::to_value(&proxy.proxy_type)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default();
+    HashMap::from([
+        ("NAME", proxy.get_name().to_string()),
+        ("IP", ip.to_string()),
+        ("PROTOCOL", protocol),
+        (
+            "SOURCE",
+            proxy
+                .sources
+                .first()
+                .map(|s| source_label(s))
+                .unwrap_or_default(),
+        ),
+        ("DELAY", delay.map(|d| d.to_string()).unwrap_or_default()),
+    ])
+}
+
+/// IP 查询结果相关的变量，查询接口返回的 None 等占位值视为缺失
+pub fn add_ip_detail_vars(vars: &mut HashMap<&'static str, String>, detail: &IpDetail) {
+    let clean = |value: &str| {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("none") || value.eq_ignore_ascii_case("null") {
+            String::new()
+        } else {
+            value.to_string()
+        }
+    };
+    let country_code = clean(&detail.country_code);
+    vars.insert("FLAG", flag_emoji(&country_code));
+    vars.insert("COUNTRYCODE", country_code);
+    vars.insert("COUNTRY", clean(&detail.country));
+    vars.insert("REGION", clean(&detail.region));
+    vars.insert("CITY", clean(&detail.city));
+    vars.insert("ISP", clean(&detail.isp));
+    vars.insert(
+        "ASN",
+        detail
+            .asn
+            .map(|asn| format!("AS{}", asn))
+            .unwrap_or_default(),
+    );
+}
+
+/// 无法查询 IP 信息时，从节点原名称推断国家相关的变量
+pub fn add_inferred_region_vars(vars: &mut HashMap<&'static str, String>, name: &str) {
+    if let Some(code) = region::infer_country_code(name) {
+        let country = region::find_region(&code).map_or("", |r| r.name);
+        vars.insert("FLAG", flag_emoji(&code));
+        vars.insert("COUNTRY", country.to_string());
+        vars.insert("COUNTRYCODE", code);
+    }
+}
+
+/// 能力标签相关的变量，TAGS 为所有可用能力以 _ 连接
+pub fn add_capability_vars(vars: &mut HashMap<&'static str, String>, gemini: bool, claude: bool) {
+    let gemini = if gemini { "Gemini" } else { "" };
+    let claude = if claude { "Claude" } else { "" };
+    let tags = [gemini, claude]
+        .into_iter()
+        .filter(|tag| !tag.is_empty())
+        .collect::<Vec<_>>()
+        .join("_");
+    vars.insert("GEMINI", gemini.to_string());
+    vars.insert("CLAUDE", claude.to_string());
+    vars.insert("TAGS", tags);
+}
+
+/// 国家代码转国旗 emoji，如 HK -> 🇭🇰
+pub fn flag_emoji(country_code: &str) -> String {
+    let code = country_code.trim().to_uppercase();
+    if code.len() != 2 || !code.chars().all(|c| c.is_ascii_uppercase()) {
+        return String::new();
+    }
+    code.chars()
+        .filter_map(|c| char::from_u32(0x1F1E6 + (c as u32 - 'A' as u32)))
+        .collect()
+}
+
+/// 来源的简短名称，网络地址取域名，本地文件取文件名
+pub fn source_label(source: &str) -> String {
+    if let Ok(url) = reqwest::Url::parse(source) {
+        if let Some(host) = url.host_str() {
+            return host.to_string();
+        }
+    }
+    std::path::Path::new(source)
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| source.to_string())
+}
+
+/// 带宽转为可读格式，单位为 KB/s
+pub fn format_speed(kb_per_sec: f64) -> String {
+    if kb_per_sec >= 1024.0 {
+        format!("{:.1}MB/s", kb_per_sec / 1024.0)
+    } else {
+        format!("{:.0}KB/s", kb_per_sec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> HashMap<&'static str, String> {
+        HashMap::from([
+            ("IP", "1.2.3.4".to_string()),
+            ("COUNTRYCODE", "hk".to_string()),
+            ("ISP", "Vertex Connectivity LLC".to_string()),
+            ("CITY", "".to_string()),
+            ("DELAY", "123".to_string()),
+            ("INDEX", "7".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_render() {
+        let template =
+            NameTemplate::parse("${COUNTRYCODE|upper}_${CITY}_${ISP|truncate:6}").unwrap();
+        assert_eq!(template.render(&vars()), "HK__Vertex");
+
+        let template = NameTemplate::parse(
+            "${COUNTRYCODE|upper}$[_${CITY}]$[_${ISP|truncate:6}]$[ ${DELAY}ms]",
+        )
+        .unwrap();
+        assert_eq!(template.render(&vars()), "HK_Vertex 123ms");
+
+        let template = NameTemplate::parse("${CITY|default:未知} ${INDEX|pad:3} $$5").unwrap();
+        assert_eq!(template.render(&vars()), "未知 007 $5");
+
+        let template = NameTemplate::parse("$[${SPEED}$[ ${DELAY}]]").unwrap();
+        assert_eq!(template.render(&vars()), "");
+        assert!(template.uses_any(&["DELAY"]));
+        assert!(!template.uses_any(&["TAGS"]));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert!(NameTemplate::parse("${UNKNOWN}").is_err());
+        assert!(NameTemplate::parse("${IP|reverse}").is_err());
+        assert!(NameTemplate::parse("${IP").is_err());
+        assert!(NameTemplate::parse("$[${IP}").is_err());
+        assert_eq!(
+            NameTemplate::parse("[HK] ${IP}").unwrap().render(&vars()),
+            "[HK] 1.2.3.4"
+        );
+    }
+
+    #[test]
+    fn test_node_vars() {
+        let mut proxy = Proxy::from_link(
+            "ss://YWVzLTEyOC1nY206ZDljNTc3MzI4ZmIzNDlmZQ==@120.232.73.68:40676#HK".to_string(),
+        )
+        .unwrap();
+        proxy.add_source("https://example.com/sub?token=1");
+        let mut vars = node_vars(&proxy, &"1.2.3.4".parse().unwrap(), Some(88));
+        add_ip_detail_vars(
+            &mut vars,
+            &IpDetail {
+                country_code: "JP".to_string(),
+                city: "None".to_string(),
+                asn: Some(2516),
+                ..Default::default()
+            },
+        );
+        add_capability_vars(&mut vars, true, true);
+        let template = NameTemplate::parse(
+            "${FLAG} ${COUNTRYCODE}$[_${CITY}] ${ASN} ${PROTOCOL}@${SOURCE} ${DELAY}ms_${TAGS}",
+        )
+        .unwrap();
+        assert_eq!(
+            template.render(&vars),
+            "🇯🇵 JP AS2516 ss@example.com 88ms_Gemini_Claude"
+        );
+    }
+
+    #[test]
+    fn test_inferred_region_vars() {
+        let mut vars = HashMap::new();
+        add_inferred_region_vars(&mut vars, "🇭🇰香港 01 | 专线");
+        assert_eq!(vars["COUNTRYCODE"], "HK");
+        assert_eq!(vars["COUNTRY"], "Hong Kong");
+        assert_eq!(vars["FLAG"], "🇭🇰");
+    }
+
+    #[test]
+    fn test_helpers() {
+        assert_eq!(flag_emoji("hk"), "🇭🇰");
+        assert_eq!(flag_emoji("None"), "");
+        assert_eq!(
+            source_label("https://raw.githubusercontent.com/a/b.yaml"),
+            "raw.githubusercontent.com"
+        );
+        assert_eq!(source_label("./subs/raw/free.yaml"), "free");
+        assert_eq!(format_speed(2048.0), "2.0MB/s");
+        assert_eq!(format_speed(512.4), "512KB/s");
+    }
+}
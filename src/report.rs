@@ -0,0 +1,51 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::diagnosis::NodeFailure;
+use crate::store::now;
+use crate::store::save_json;
+
+/// 单次运行的汇总结果，运行结束后保存在 subs/report.json
+#[derive(Debug, Default, Serialize)]
+pub struct RunReport {
+    pub started_at: u64,
+    pub finished_at: u64,
+    // 解析后待测试的节点数
+    pub tested: usize,
+    // 连通性测试通过的节点数
+    pub available: usize,
+    // 最终写入 release 文件的节点数
+    pub released: usize,
+    // 出口 IP 相同而被合并的节点
+    pub exit_ip_aliases: Vec<ExitIpAlias>,
+    // 本次测试失败但在宽限期内保留的节点
+    pub degraded: Vec<String>,
+    // 不满足安全阈值而未发布时的原因
+    pub aborted: Option<String>,
+    // 测试失败的节点及归类后的原因，名称为重命名前的原始名称
+    pub failures: BTreeMap<String, NodeFailure>,
+}
+
+/// 同一出口 IP 下保留和被合并的节点名称，名称为重命名前的原始名称
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExitIpAlias {
+    pub ip: String,
+    pub kept: Vec<String>,
+    pub collapsed: Vec<String>,
+}
+
+impl RunReport {
+    pub fn new() -> Self {
+        RunReport {
+            started_at: now(),
+            ..Default::default()
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<()> {
+        self.finished_at = now();
+        save_json(path, self)
+    }
+}
@@ -0,0 +1,86 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_yaml::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SafetyConfig {
+    // 可用节点少于该数量时不发布
+    pub min_nodes: usize,
+    // 可用节点相比上次发布减少的比例超过该值时不发布，1 表示不限制
+    pub max_drop: f64,
+}
+
+impl Default for SafetyConfig {
+    fn default() -> Self {
+        SafetyConfig {
+            min_nodes: 1,
+            max_drop: 0.8,
+        }
+    }
+}
+
+/// 读取上次发布的 release 文件中的节点数，文件不存在或格式错误时返回 None
+pub fn count_released_proxies<P: AsRef<Path>>(path: P) -> Option<usize> {
+    let content = fs::read_to_string(path).ok()?;
+    let yaml: Value = serde_yaml::from_str(&content).ok()?;
+    yaml.get("proxies")?.as_sequence().map(|p| p.len())
+}
+
+/// 检查本次可用节点数是否满足安全阈值，不满足时返回原因
+pub fn check(config: &SafetyConfig, count: usize, previous: Option<usize>) -> Result<(), String> {
+    if count < config.min_nodes {
+        return Err(format!(
+            "可用节点数 {} 少于最小节点数 {}",
+            count, config.min_nodes
+        ));
+    }
+    if let Some(previous) = previous.filter(|p| *p > 0) {
+        let drop = 1.0 - count as f64 / previous as f64;
+        if drop > config.max_drop {
+            return Err(format!(
+                "可用节点数 {} 相比上次发布的 {} 减少了 {:.0}%，超过上限 {:.0}%",
+                count,
+                previous,
+                drop * 100.0,
+                config.max_drop * 100.0
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let config = SafetyConfig {
+            min_nodes: 3,
+            max_drop: 0.5,
+        };
+        assert!(check(&config, 2, None).is_err());
+        assert!(check(&config, 3, None).is_ok());
+        assert!(check(&config, 5, Some(10)).is_ok());
+        assert!(check(&config, 4, Some(10)).is_err());
+        assert!(check(&config, 20, Some(10)).is_ok());
+        assert!(check(&config, 3, Some(0)).is_ok());
+    }
+
+    #[test]
+    fn test_count_released_proxies() {
+        let path = std::env::temp_dir().join("clash-butler-safety-test.yaml");
+        fs::write(
+            &path,
+            "proxies:\n  - name: a\n  - name: b\nproxy-groups: []\n",
+        )
+        .unwrap();
+        assert_eq!(count_released_proxies(&path), Some(2));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(count_released_proxies(&path), None);
+    }
+}
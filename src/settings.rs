@@ -1,10 +1,16 @@
 use config::Config;
 use config::ConfigError;
 use config::File;
+use proxrs::sub::DedupPolicy;
+use proxrs::transform::TransformStep;
 use serde::Deserialize;
 
 use crate::clash::DelayTestConfig;
+use crate::release::ReleaseConfig;
+use crate::safety::SafetyConfig;
 use crate::speedtest::SpeedTestConfig;
+use crate::stats::SourceStatsConfig;
+use crate::sticky::StickyConfig;
 
 #[derive(Deserialize, Debug)]
 #[allow(unused)]
@@ -15,9 +21,41 @@ pub struct Settings {
     pub rename_pattern: String,
     pub need_add_pool: bool,
     pub test_group_size: usize,
+    #[serde(default = "default_test_parallelism")]
+    pub test_parallelism: usize,
     pub pools: Vec<String>,
     pub connect_test: DelayTestConfig,
     pub speed_test: SpeedTestConfig,
+    #[serde(default)]
+    pub source_stats: SourceStatsConfig,
+    #[serde(default)]
+    pub sticky: StickyConfig,
+    #[serde(default)]
+    pub safety: SafetyConfig,
+    #[serde(default)]
+    pub release: ReleaseConfig,
+    #[serde(default)]
+    pub transforms: Vec<TransformStep>,
+    #[serde(default)]
+    pub dedup_policy: DedupPolicy,
+    #[serde(default)]
+    pub ad_phrases: Vec<String>,
+    #[serde(default = "default_max_per_exit_ip")]
+    pub max_per_exit_ip: usize,
+    #[serde(default = "default_probe_concurrency")]
+    pub probe_concurrency: usize,
+}
+
+fn default_max_per_exit_ip() -> usize {
+    1
+}
+
+fn default_test_parallelism() -> usize {
+    4
+}
+
+fn default_probe_concurrency() -> usize {
+    16
 }
 
 impl Settings {
@@ -14,8 +14,7 @@ pub struct SpeedTestConfig {
     pub timeout: u16,
 }
 
-#[allow(dead_code)]
-async fn test_download(
+pub async fn test_download(
     url: &str,
     timeout: Duration,
     proxy_url: Option<&str>,
@@ -0,0 +1,291 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use proxrs::protocol::Proxy;
+use proxrs::sub::SourceInfo;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::info;
+use tracing::warn;
+
+use crate::store::load_json;
+use crate::store::now;
+use crate::store::save_json;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SourceStatsConfig {
+    // 连续多少次运行没有可用节点后标记为失效来源
+    pub dead_threshold: u32,
+    // 是否自动跳过被标记为失效的来源
+    pub auto_disable: bool,
+}
+
+impl Default for SourceStatsConfig {
+    fn default() -> Self {
+        SourceStatsConfig {
+            dead_threshold: 5,
+            auto_disable: false,
+        }
+    }
+}
+
+/// 单个来源在本次运行中的产出
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceYield {
+    pub source: String,
+    pub total: usize,
+    pub valid: usize,
+}
+
+impl SourceYield {
+    pub fn yield_rate(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.valid as f64 / self.total as f64
+        }
+    }
+}
+
+/// 单个来源的历史统计
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SourceRecord {
+    pub runs: u32,
+    pub last_total: usize,
+    pub last_valid: usize,
+    pub total_sum: usize,
+    pub valid_sum: usize,
+    pub consecutive_dead_runs: u32,
+    pub last_valid_at: Option<u64>,
+    pub dead: bool,
+    // 最近一次从信息节点中提取的流量、到期时间等
+    #[serde(default)]
+    pub info: SourceInfo,
+}
+
+/// 持久化在本地的来源统计，跨多次运行累积
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SourceStats {
+    pub sources: BTreeMap<String, SourceRecord>,
+}
+
+impl SourceStats {
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        load_json(path)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        save_json(path, self)
+    }
+
+    pub fn set_info(&mut self, source: &str, info: SourceInfo) {
+        self.sources.entry(source.to_string()).or_default().info = info;
+    }
+
+    pub fn is_dead(&self, source: &str) -> bool {
+        self.sources.get(source).is_some_and(|r| r.dead)
+    }
+
+    /// 合并本次运行结果，返回本次新标记为失效的来源
+    /// 所有来源都没有可用节点时多半是本地网络异常，不计入各来源的统计
+    pub fn update(&mut self, yields: &[SourceYield], config: &SourceStatsConfig) -> Vec<String> {
+        let mut new_dead = Vec::new();
+        if yields.iter().all(|y| y.valid == 0) {
+            return new_dead;
+        }
+        let now = now();
+        for y in yields {
+            let record = self.sources.entry(y.source.clone()).or_default();
+            record.runs += 1;
+            record.last_total = y.total;
+            record.last_valid = y.valid;
+            record.total_sum += y.total;
+            record.valid_sum += y.valid;
+            if y.valid > 0 {
+                record.consecutive_dead_runs = 0;
+                record.last_valid_at = Some(now);
+                record.dead = false;
+            } else {
+                record.consecutive_dead_runs += 1;
+                if !record.dead && record.consecutive_dead_runs >= config.dead_threshold {
+                    record.dead = true;
+                    new_dead.push(y.source.clone());
+                }
+            }
+        }
+        new_dead
+    }
+}
+
+/// 统计每个来源解析出的节点数和测试后可用的节点数
+/// sources 为配置中的来源，没有解析出任何节点的来源也会计入
+pub fn collect_yields(sources: &[String], parsed: &[Proxy], valid: &[Proxy]) -> Vec<SourceYield> {
+    let mut yields: BTreeMap<String, SourceYield> = sources
+        .iter()
+        .map(|s| {
+            (
+                s.clone(),
+                SourceYield {
+                    source: s.clone(),
+                    total: 0,
+                    valid: 0,
+                },
+            )
+        })
+        .collect();
+    for proxy in parsed {
+        for source in &proxy.sources {
+            yields
+                .entry(source.clone())
+                .or_insert_with(|| SourceYield {
+                    source: source.clone(),
+                    total: 0,
+                    valid: 0,
+                })
+                .total += 1;
+        }
+    }
+    for proxy in valid {
+        for source in &proxy.sources {
+            if let Some(y) = yields.get_mut(source) {
+                y.valid += 1;
+            }
+        }
+    }
+    yields.into_values().collect()
+}
+
+pub fn report(yields: &[SourceYield], stats: &SourceStats) {
+    info!("各来源节点统计：");
+    for y in yields {
+        let runs = stats.sources.get(&y.source).map_or(0, |r| r.runs);
+        info!(
+            "「{}」节点 {} 个，可用 {} 个，有效率 {:.1}%，累计运行 {} 次",
+            y.source,
+            y.total,
+            y.valid,
+            y.yield_rate() * 100.0,
+            runs
+        );
+        if let Some(record) = stats.sources.get(&y.source) {
+            let info = &record.info;
+            if info.remaining_traffic.is_some() || info.expire.is_some() {
+                info!(
+                    "「{}」剩余流量 {}，到期时间 {}",
+                    y.source,
+                    info.remaining_traffic.as_deref().unwrap_or("未知"),
+                    info.expire.as_deref().unwrap_or("未知")
+                );
+            }
+        }
+        if stats.is_dead(&y.source) {
+            warn!("「{}」已连续多次无可用节点，标记为失效来源", y.source);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proxrs::testing;
+
+    use super::*;
+
+    fn proxy(port: u16, name: &str, sources: &[&str]) -> Proxy {
+        let mut proxy = testing::proxy(port, name);
+        for source in sources {
+            proxy.add_source(source);
+        }
+        proxy
+    }
+
+    #[test]
+    fn test_collect_yields() {
+        let a = proxy(40676, "a", &["sub1", "sub2"]);
+        let b = proxy(40677, "b", &["sub1"]);
+        let sources = vec!["sub1".to_string(), "sub3".to_string()];
+        let yields = collect_yields(&sources, &[a.clone(), b], &[a]);
+        assert_eq!(
+            yields,
+            vec![
+                SourceYield {
+                    source: "sub1".to_string(),
+                    total: 2,
+                    valid: 1
+                },
+                SourceYield {
+                    source: "sub2".to_string(),
+                    total: 1,
+                    valid: 1
+                },
+                SourceYield {
+                    source: "sub3".to_string(),
+                    total: 0,
+                    valid: 0
+                },
+            ]
+        );
+        assert_eq!(yields[0].yield_rate(), 0.5);
+        assert_eq!(yields[2].yield_rate(), 0.0);
+    }
+
+    #[test]
+    fn test_mark_dead_source() {
+        let config = SourceStatsConfig {
+            dead_threshold: 2,
+            auto_disable: true,
+        };
+        let mut stats = SourceStats::default();
+        let dead = vec![
+            SourceYield {
+                source: "pool".to_string(),
+                total: 10,
+                valid: 0,
+            },
+            SourceYield {
+                source: "sub".to_string(),
+                total: 5,
+                valid: 2,
+            },
+        ];
+        assert!(stats.update(&dead, &config).is_empty());
+        assert_eq!(stats.update(&dead, &config), vec!["pool".to_string()]);
+        assert!(stats.is_dead("pool"));
+        assert!(stats.update(&dead, &config).is_empty());
+
+        let alive = vec![SourceYield {
+            source: "pool".to_string(),
+            total: 10,
+            valid: 3,
+        }];
+        stats.update(&alive, &config);
+        let record = &stats.sources["pool"];
+        assert!(!record.dead);
+        assert_eq!(record.runs, 4);
+        assert_eq!(record.consecutive_dead_runs, 0);
+        assert_eq!(record.valid_sum, 3);
+    }
+
+    #[test]
+    fn test_outage_not_counted() {
+        let config = SourceStatsConfig {
+            dead_threshold: 1,
+            auto_disable: true,
+        };
+        let mut stats = SourceStats::default();
+        let outage = vec![
+            SourceYield {
+                source: "pool".to_string(),
+                total: 10,
+                valid: 0,
+            },
+            SourceYield {
+                source: "sub".to_string(),
+                total: 5,
+                valid: 0,
+            },
+        ];
+        assert!(stats.update(&outage, &config).is_empty());
+        assert!(stats.sources.is_empty());
+    }
+}
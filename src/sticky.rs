@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::path::Path;
+
+use proxrs::protocol::Proxy;
+use proxrs::sub::SubManager;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::info;
+use tracing::warn;
+
+use crate::store::load_json;
+use crate::store::now;
+use crate::store::save_json;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StickyConfig {
+    // 已发布的节点连续多少次运行测试失败后才从 release 中移除，0 表示不保留
+    pub grace_runs: u32,
+    // 已发布的节点首次失败后最多保留多少小时，0 表示不限制
+    pub grace_hours: u64,
+    // 宽限期内保留的节点在 release 中的名称后缀，为空时不添加
+    pub degraded_suffix: String,
+}
+
+impl Default for StickyConfig {
+    fn default() -> Self {
+        StickyConfig {
+            grace_runs: 2,
+            grace_hours: 0,
+            degraded_suffix: " [降级]".to_string(),
+        }
+    }
+}
+
+/// 已发布节点的状态
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StickyRecord {
+    // 节点的 Clash 配置
+    pub proxy: String,
+    pub sources: Vec<String>,
+    pub failed_runs: u32,
+    pub first_failed_at: Option<u64>,
+    pub last_ok_at: u64,
+}
+
+/// 之前发布过的节点，按节点指纹记录，保存在 subs/sticky.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StickyState {
+    pub nodes: BTreeMap<String, StickyRecord>,
+}
+
+impl StickyState {
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        load_json(path)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        save_json(path, self)
+    }
+
+    /// 记录本次发布的节点，并将仍在宽限期内的失败节点加回 release
+    /// 只有 connect_failed 中本次连通性测试失败的节点可以加回，按出口 IP 去重、
+    /// AI 服务检测等主动过滤掉的节点以及本次没有出现的节点直接移除
+    /// 返回加回的降级节点
+    pub fn apply(
+        &mut self,
+        proxies: &mut Vec<Proxy>,
+        connect_failed: &HashSet<String>,
+        config: &StickyConfig,
+    ) -> Vec<Proxy> {
+        let now = now();
+        let mut released = HashSet::new();
+        for proxy in proxies.iter() {
+            let Ok(json) = proxy.to_json() else {
+                continue;
+            };
+            let fingerprint = SubManager::fingerprint(proxy);
+            released.insert(fingerprint.clone());
+            self.nodes.insert(
+                fingerprint,
+                StickyRecord {
+                    proxy: json,
+                    sources: proxy.sources.clone(),
+                    failed_runs: 0,
+                    first_failed_at: None,
+                    last_ok_at: now,
+                },
+            );
+        }
+
+        let mut degraded = Vec::new();
+        self.nodes.retain(|fingerprint, record| {
+            if released.contains(fingerprint) {
+                return true;
+            }
+            if !connect_failed.contains(fingerprint) {
+                return false;
+            }
+            record.failed_runs += 1;
+            let first_failed_at = *record.first_failed_at.get_or_insert(now);
+            let expired = config.grace_hours > 0
+                && now.saturating_sub(first_failed_at) > config.grace_hours * 3600;
+            if record.failed_runs > config.grace_runs || expired {
+                return false;
+            }
+            match Proxy::from_json(&record.proxy) {
+                Ok(mut proxy) => {
+                    for source in &record.sources {
+                        proxy.add_source(source);
+                    }
+                    degraded.push(proxy);
+                    true
+                }
+                Err(e) => {
+                    warn!("无法还原已发布的节点, {}", e);
+                    false
+                }
+            }
+        });
+        for proxy in &degraded {
+            info!("「{}」本次测试失败，在宽限期内保留", proxy.get_name());
+        }
+        proxies.extend(degraded.iter().cloned());
+        degraded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proxrs::testing;
+
+    use super::*;
+
+    fn proxy(port: u16) -> Proxy {
+        testing::proxy(port, &format!("node{}", port))
+    }
+
+    fn failed(ports: &[u16]) -> HashSet<String> {
+        ports
+            .iter()
+            .map(|&port| SubManager::fingerprint(&proxy(port)))
+            .collect()
+    }
+
+    #[test]
+    fn test_grace_runs() {
+        let config = StickyConfig {
+            grace_runs: 2,
+            grace_hours: 0,
+            ..Default::default()
+        };
+        let mut state = StickyState::default();
+        let mut release = vec![proxy(1), proxy(2)];
+        assert!(state.apply(&mut release, &failed(&[]), &config).is_empty());
+
+        for _ in 0..2 {
+            let mut release = vec![proxy(1)];
+            let degraded = state.apply(&mut release, &failed(&[2]), &config);
+            assert_eq!(degraded.len(), 1);
+            assert_eq!(degraded[0].get_name(), "node2");
+            assert_eq!(release.len(), 2);
+        }
+
+        let mut release = vec![proxy(1)];
+        assert!(state.apply(&mut release, &failed(&[2]), &config).is_empty());
+        assert_eq!(release.len(), 1);
+        assert_eq!(state.nodes.len(), 1);
+    }
+
+    #[test]
+    fn test_filtered_nodes_not_kept() {
+        let config = StickyConfig::default();
+        let mut state = StickyState::default();
+        state.apply(&mut vec![proxy(1), proxy(2)], &failed(&[]), &config);
+
+        // node2 通过了连通性测试，但因出口 IP 与 node1 相同被去重，不在宽限期内保留
+        let mut release = vec![proxy(1)];
+        assert!(state.apply(&mut release, &failed(&[]), &config).is_empty());
+        assert_eq!(release.len(), 1);
+        assert_eq!(state.nodes.len(), 1);
+    }
+
+    #[test]
+    fn test_recover_resets_failures() {
+        let config = StickyConfig::default();
+        let mut state = StickyState::default();
+        state.apply(&mut vec![proxy(1)], &failed(&[]), &config);
+        state.apply(&mut vec![], &failed(&[1]), &config);
+        state.apply(&mut vec![proxy(1)], &failed(&[]), &config);
+        let record = state.nodes.values().next().unwrap();
+        assert_eq!(record.failed_runs, 0);
+        assert!(record.first_failed_at.is_none());
+    }
+}
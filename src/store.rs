@@ -0,0 +1,28 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// 当前 Unix 时间戳，单位为秒
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// 读取跨运行保存的 JSON 状态，文件不存在或格式不正确时返回默认值
+pub fn load_json<T: DeserializeOwned + Default, P: AsRef<Path>>(path: P) -> T {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_json<T: Serialize, P: AsRef<Path>>(path: P, value: &T) -> io::Result<()> {
+    fs::write(path, serde_json::to_string_pretty(value)?)
+}